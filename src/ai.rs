@@ -5,28 +5,54 @@ use bevy::prelude::*;
 
 use crate::{
     combat::Combat,
-    config::TILE_SIZE,
-    entities::{Body, Name, Player, Speed},
+    entities::{Body, Level, Name, Player, Speed},
+    map::{TileMap, TilePos},
+    network::is_authoritative,
+    states::AppState,
+    visibility::has_line_of_sight,
 };
 
 pub struct AiPlugin;
 
 impl Plugin for AiPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(find_an_enemy.system())
-            .add_system(monster_ai.system());
+        app.add_event::<SpawnMonsterEvent>();
+        if is_authoritative(app) {
+            app.add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(spawn_monster_events.system())
+                    .with_system(find_an_enemy.system())
+                    .with_system(monster_ai.system())
+                    .with_system(respawn_monsters.system()),
+            );
+        }
     }
 }
 
 pub struct ExperiencePoints(pub u32);
 
+/// Item titles a monster may drop on death, each with its chance in percent.
+#[derive(Debug, Clone)]
+pub struct Loot(pub Vec<(String, f32)>);
+
+impl Default for Loot {
+    fn default() -> Self {
+        Loot(vec![
+            ("Health Potion".to_string(), 25.),
+            ("Arrow".to_string(), 50.),
+        ])
+    }
+}
+
 #[derive(Bundle)]
 pub struct MonsterBundle {
     name: Name,
     monster: Monster,
     body: Body,
     speed: Speed,
+    level: Level,
     experience: ExperiencePoints,
+    loot: Loot,
 
     #[bundle]
     combat: Combat,
@@ -56,11 +82,36 @@ impl Default for MonsterBundle {
                 ..Default::default()
             },
             combat: Combat::default(),
+            level: Level(1),
             experience: ExperiencePoints(100),
+            loot: Loot::default(),
         }
     }
 }
 
+pub fn spawn_monster(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    name: &str,
+    translation: Vec3,
+    tile_size: f32,
+) -> Entity {
+    commands
+        .spawn()
+        .insert_bundle(MonsterBundle::new(name))
+        .insert(TilePos::from_world(translation, tile_size))
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                size: Vec2::new(tile_size, tile_size),
+                ..Default::default()
+            },
+            material: materials.add(Color::BLUE.into()),
+            transform: Transform::from_translation(translation),
+            ..Default::default()
+        })
+        .id()
+}
+
 pub struct Monster {
     pub enemy: Option<Entity>,
     pub vision_range: f32,
@@ -75,13 +126,88 @@ impl Default for Monster {
     }
 }
 
+pub struct SpawnMonsterEvent {
+    pub name: String,
+    pub tile: TilePos,
+}
+
+fn spawn_monster_events(
+    mut commands: Commands,
+    tile_map: Res<TileMap>,
+    mut events: EventReader<SpawnMonsterEvent>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for event in events.iter() {
+        spawn_monster(
+            &mut commands,
+            &mut materials,
+            &event.name,
+            event.tile.to_world(tile_map.tile_size, 0.),
+            tile_map.tile_size,
+        );
+    }
+}
+
+pub struct SpawnPoint {
+    pub monster: String,
+    pub position: Vec3,
+    pub timer: Timer,
+    pub spawned: Option<Entity>,
+}
+
+impl SpawnPoint {
+    pub fn new(monster: &str, position: Vec3, respawn: Duration) -> Self {
+        SpawnPoint {
+            monster: monster.to_string(),
+            position,
+            timer: Timer::new(respawn, false),
+            spawned: None,
+        }
+    }
+}
+
+fn respawn_monsters(
+    mut commands: Commands,
+    time: Res<Time>,
+    tile_map: Res<TileMap>,
+    mut spawn_points: Query<&mut SpawnPoint>,
+    monsters: Query<&Monster>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for mut spawn_point in spawn_points.iter_mut() {
+        if let Some(e) = spawn_point.spawned {
+            if monsters.get(e).is_ok() {
+                continue;
+            }
+            spawn_point.spawned = None;
+            spawn_point.timer.reset();
+        }
+        spawn_point.timer.tick(time.delta());
+        if spawn_point.timer.finished() {
+            let position = spawn_point.position;
+            let monster = spawn_monster(
+                &mut commands,
+                &mut materials,
+                &spawn_point.monster,
+                position,
+                tile_map.tile_size,
+            );
+            spawn_point.spawned = Some(monster);
+        }
+    }
+}
+
+/// Monsters go after a player within their vision range that no wall hides.
 fn find_an_enemy(
-    mut monsters: Query<(&Transform, &mut Monster)>,
-    players: Query<(Entity, &Name, &Transform), With<Player>>,
+    tile_map: Res<TileMap>,
+    mut monsters: Query<(&TilePos, &mut Monster)>,
+    players: Query<(Entity, &Name, &TilePos), With<Player>>,
 ) {
-    for (m_transform, mut monster) in monsters.iter_mut() {
-        for (p_entity, _, p_transform) in players.iter() {
-            if m_transform.translation.distance(p_transform.translation) < monster.vision_range {
+    for (m_tile, mut monster) in monsters.iter_mut() {
+        for (p_entity, _, p_tile) in players.iter() {
+            if m_tile.world_distance(*p_tile, tile_map.tile_size) < monster.vision_range
+                && has_line_of_sight(&tile_map, *m_tile, *p_tile)
+            {
                 monster.enemy = Some(p_entity);
                 break;
             }
@@ -91,15 +217,15 @@ fn find_an_enemy(
 }
 
 fn monster_ai(
-    mut monsters: Query<(Entity, &mut Monster, &Transform, &mut Speed)>,
-    players: Query<(Entity, &Player, &Transform)>,
+    mut monsters: Query<(Entity, &mut Monster, &TilePos, &mut Speed)>,
+    players: Query<(Entity, &Player, &TilePos)>,
     mut move_events: EventWriter<MoveEvent>,
 ) {
-    for (m_entity, monster, m_transform, _) in monsters.iter_mut() {
-        for (p_entity, _, p_transform) in players.iter() {
+    for (m_entity, monster, m_tile, _) in monsters.iter_mut() {
+        for (p_entity, _, p_tile) in players.iter() {
             if let Some(me) = monster.enemy {
                 if me == p_entity {
-                    let vec = follow_enemy(*m_transform, *p_transform);
+                    let vec = follow_enemy(*m_tile, *p_tile);
                     move_events.send(MoveEvent(m_entity, vec));
                 }
             }
@@ -107,25 +233,7 @@ fn monster_ai(
     }
 }
 
-fn follow_enemy(m_transform: Transform, p_transform: Transform) -> Vec3 {
-    let x;
-    let y;
-    let z;
-    if m_transform.translation.x < p_transform.translation.x {
-        x = TILE_SIZE;
-    } else if m_transform.translation.x > p_transform.translation.x {
-        x = -TILE_SIZE;
-    } else {
-        x = 0.;
-    }
-    if m_transform.translation.y < p_transform.translation.y {
-        y = TILE_SIZE;
-    } else if m_transform.translation.y > p_transform.translation.y {
-        y = -TILE_SIZE;
-    } else {
-        y = 0.;
-    }
-    z = m_transform.translation.z;
-
-    Vec3::new(x, y, z)
+fn follow_enemy(m_tile: TilePos, p_tile: TilePos) -> IVec2 {
+    let delta = m_tile.delta(p_tile);
+    IVec2::new(delta.x.signum(), delta.y.signum())
 }
@@ -0,0 +1,24 @@
+use bevy::prelude::*;
+
+use gamedev::{
+    network::{add_headless_plugins, NetworkMode},
+    states::AppState,
+    SimulationPlugins,
+};
+
+/// Dedicated server: the simulation without window, input or interface.
+fn main() {
+    let network = match NetworkMode::server_from_args() {
+        Ok(network) => network,
+        Err(e) => {
+            println!("Invalid server address: {:?}", e);
+            return;
+        }
+    };
+    let mut app = App::build();
+    add_headless_plugins(&mut app);
+    app.add_state(AppState::Playing)
+        .insert_resource(network)
+        .add_plugins(SimulationPlugins)
+        .run();
+}
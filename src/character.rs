@@ -0,0 +1,259 @@
+use bevy::prelude::*;
+
+use crate::{
+    combat::{Attack, Defense, Health, Mana, DAMAGE_TYPES},
+    entities::{CurrentExperience, Level, Name, NextLevelExperience},
+    input_map::{Action, Actions},
+    inventory::carried_weight,
+    item::{AttributeType, Capacity, Equipments, Item},
+    skills::{Skills, SKILLS},
+    states::AppState,
+    vocation::Vocation,
+    LocalPlayer,
+};
+
+const EXPERIENCE_BAR_WIDTH: f32 = 180.;
+
+pub struct CharacterPlugin;
+
+impl Plugin for CharacterPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(toggle_character_panel.system().after("actions")),
+            )
+            .add_system(character_panel.system());
+    }
+}
+
+pub struct CharacterPanel;
+
+/// Name, level and experience above the experience bar.
+pub struct CharacterHeader;
+
+/// Combat statistics below the experience bar.
+pub struct CharacterStats;
+
+pub struct ExperienceBarFill;
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/font.ttf"),
+        font_size: 12.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(240.),
+                    left: Val::Px(5.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(4.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.2, 0.2, 0.2, 0.9).into()),
+            ..Default::default()
+        })
+        .insert(Interaction::default())
+        .insert(CharacterPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section("", text_style.clone(), Default::default()),
+                    ..Default::default()
+                })
+                .insert(CharacterHeader);
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(EXPERIENCE_BAR_WIDTH), Val::Px(6.)),
+                        margin: Rect::all(Val::Px(2.)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgba(0., 0., 0., 0.8).into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                                ..Default::default()
+                            },
+                            material: materials.add(Color::GOLD.into()),
+                            ..Default::default()
+                        })
+                        .insert(ExperienceBarFill);
+                });
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section("", text_style, Default::default()),
+                    ..Default::default()
+                })
+                .insert(CharacterStats);
+        });
+}
+
+fn toggle_character_panel(
+    actions: Res<Actions>,
+    mut panels: Query<&mut Style, With<CharacterPanel>>,
+) {
+    if !actions.just_pressed(Action::OpenCharacter) {
+        return;
+    }
+    for mut style in panels.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            Display::Flex => Display::None,
+        };
+    }
+}
+
+fn character_panel(
+    player: Res<LocalPlayer>,
+    players: Query<(
+        &Name,
+        &Level,
+        &CurrentExperience,
+        &NextLevelExperience,
+        &Health,
+        &Mana,
+        &Attack,
+        &Defense,
+        &Vocation,
+        &Capacity,
+        &Equipments,
+        &Vec<Item>,
+    )>,
+    skills: Query<&Skills>,
+    mut headers: Query<&mut Text, (With<CharacterHeader>, Without<CharacterStats>)>,
+    mut stats: Query<&mut Text, (With<CharacterStats>, Without<CharacterHeader>)>,
+    mut bars: Query<&mut Style, With<ExperienceBarFill>>,
+) {
+    let (
+        name,
+        level,
+        current,
+        next,
+        health,
+        mana,
+        attack,
+        defense,
+        vocation,
+        capacity,
+        equipments,
+        inventory,
+    ) = match player.0.map(|player| players.get(player)) {
+        Some(Ok(player)) => player,
+        _ => return,
+    };
+    // Players at the max level need no more experience.
+    let (percent, header) = if next.0 > 0 {
+        let percent = (current.0 as f32 / next.0 as f32).min(1.) * 100.;
+        let header = format!(
+            "{}\nLevel {} {}\nExperience: {} / {} ({:.0}%)",
+            name.value,
+            level.0,
+            vocation.title(),
+            current.0,
+            next.0,
+            percent
+        );
+        (percent, header)
+    } else {
+        let header = format!(
+            "{}\nLevel {} {}\nMax level",
+            name.value,
+            level.0,
+            vocation.title()
+        );
+        (100., header)
+    };
+
+    let mut lines = vec![
+        format!("Health: {:.0} / {:.0}", health.value, health.max_value),
+        format!("Mana: {:.0} / {:.0}", mana.value, mana.max_value),
+        format!(
+            "Capacity: {:.0} / {:.0} oz",
+            carried_weight(equipments, inventory),
+            capacity.0
+        ),
+        String::new(),
+    ];
+    for dtype in DAMAGE_TYPES.iter() {
+        let mut damage = equipments.get_attributes(AttributeType::Damage(*dtype));
+        if *dtype == attack.damage.dtype {
+            damage += attack.damage.value;
+        }
+        if damage > 0. {
+            lines.push(format!("Attack: {:.0} {:?}", damage, dtype));
+        }
+    }
+    lines.push(format!(
+        "Attack rate: {:.0}% every {:.1}s",
+        attack.rate,
+        attack.interval.duration().as_secs_f32()
+    ));
+    lines.push(format!("Attack range: {:.0}", attack.range));
+    lines.push(String::new());
+    lines.push(format!(
+        "Defense: {:.0}",
+        defense.value + equipments.get_attributes(AttributeType::Defense)
+    ));
+    lines.push(format!(
+        "Block: {:.0}",
+        equipments.get_attributes(AttributeType::Block)
+    ));
+    lines.push(format!(
+        "Evasion: {:.0}",
+        equipments.get_attributes(AttributeType::Evasion)
+    ));
+    if let Some(Ok(skills)) = player.0.map(|player| skills.get(player)) {
+        lines.push(String::new());
+        lines.push("Skills".to_string());
+        for skill in SKILLS.iter() {
+            lines.push(format!(
+                "{}: {} ({:.0}%)",
+                skill.title(),
+                skills.level(*skill),
+                skills.percent(*skill)
+            ));
+        }
+    }
+    lines.push(String::new());
+    lines.push("Resistances".to_string());
+    for dtype in DAMAGE_TYPES.iter() {
+        lines.push(format!(
+            "{:?}: {:.0}%",
+            dtype,
+            equipments.get_attributes(AttributeType::Resistance(*dtype))
+        ));
+    }
+    let body = lines.join("\n");
+
+    for mut text in headers.iter_mut() {
+        if text.sections[0].value != header {
+            text.sections[0].value = header.clone();
+        }
+    }
+    for mut text in stats.iter_mut() {
+        if text.sections[0].value != body {
+            text.sections[0].value = body.clone();
+        }
+    }
+    for mut style in bars.iter_mut() {
+        if style.size.width != Val::Percent(percent) {
+            style.size.width = Val::Percent(percent);
+        }
+    }
+}
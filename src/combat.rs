@@ -1,5 +1,6 @@
 extern crate rand;
 use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
 
 use bevy::{
     ecs::bundle,
@@ -7,43 +8,67 @@ use bevy::{
     render::{color, render_graph::base::MainPass},
     text::Text2dSize,
 };
-use std::{borrow::Borrow, collections::btree_map::Range, time::Duration};
+use std::{
+    borrow::Borrow,
+    collections::{btree_map::Range, HashMap},
+    time::Duration,
+};
 
 use crate::{
-    ai::ExperiencePoints,
-    entities::{CurrentExperience, Name, NextLevelExperience, Player},
-    item::{AttributeType, Equipments, Item},
-    main, LocalPlayer,
+    ai::{ExperiencePoints, Loot},
+    entities::{CurrentExperience, Level, Name, Player},
+    item::{spawn_ground_item, AttributeType, Equipments, Item, RangedWeapon},
+    levels::LevelCurve,
+    map::{TileMap, TilePos},
+    messages::{GameMessage, PlayerMessage},
+    network::is_authoritative,
+    party::{experience_receivers, shared_experience, PartyMember},
+    skills::{
+        hit_chance, holds_shield, shield_block, skill_level, weapon_damage_factor, weapon_level,
+        weapon_skill, Skill, SkillTryEvent, Skills,
+    },
+    states::AppState,
+    visibility::has_line_of_sight,
 };
 
 pub struct CombatPlugin;
 
 impl Plugin for CombatPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.insert_resource(LockedTarget(None))
-            .add_event::<AttackEvent>()
+        app.add_event::<AttackEvent>()
             .add_event::<ResistanceEvent>()
             .add_event::<MissEvent>()
             .add_event::<BlockEvent>()
             .add_event::<DamageEvent>()
             .add_event::<DeathEvent>()
+            .add_event::<KillCreditEvent>()
             .add_event::<SpawnEvent>()
-            .add_event::<CombatText>()
-            .add_system(attack_system.system())
-            .add_system(miss_system.system())
-            .add_system(attack_system.system())
-            .add_system(hit_system.system())
-            .add_system(resistance_system.system())
-            .add_system(block_system.system())
-            .add_system(damage_system.system())
-            .add_system(death_system.system())
-            .add_system(death_drop.system());
+            .add_event::<CombatText>();
+        if is_authoritative(app) {
+            app.add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(attack_system.system())
+                    .with_system(miss_system.system())
+                    .with_system(attack_system.system())
+                    .with_system(hit_system.system())
+                    .with_system(move_projectiles.system())
+                    .with_system(resistance_system.system())
+                    .with_system(block_system.system())
+                    .with_system(damage_system.system().label("damage"))
+                    .with_system(death_system.system())
+                    // Reads the dead creature before its despawn is applied.
+                    .with_system(death_drop.system().after("damage"))
+                    .with_system(forget_damage.system())
+                    .with_system(record_kills.system())
+                    .with_system(tick_status_effects.system()),
+            );
+        }
     }
 }
 
 pub struct DeathEvent {
     attacker: Entity,
-    defender: Entity,
+    pub(crate) defender: Entity,
     damage: DamageSet,
 }
 
@@ -52,10 +77,95 @@ pub struct CombatText;
 
 pub struct SpawnEvent(Entity);
 
-#[derive(Debug)]
+/// The entity a player attacks, each player locks on its own.
+#[derive(Debug, Default)]
 pub struct LockedTarget(pub Option<Entity>);
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// Tiles per second.
+const PROJECTILE_SPEED: f32 = 12.;
+
+/// Seconds a creature remembers an attacker after its last hit.
+pub const DAMAGE_MEMORY: f64 = 60.;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Contribution {
+    pub damage: f32,
+    /// `Time::seconds_since_startup` of the last hit.
+    pub last_hit: f64,
+}
+
+/// Damage received per attacker, to share the experience of a kill by contribution.
+#[derive(Debug, Default)]
+pub struct DamageTaken(pub HashMap<Entity, Contribution>);
+
+/// Sent for every player that damaged a creature before it died, for quests and the bestiary.
+#[derive(Debug)]
+pub struct KillCreditEvent {
+    pub player: Entity,
+    pub creature: String,
+}
+
+/// Kills per creature name.
+#[derive(Debug, Default, Clone)]
+pub struct Bestiary(pub HashMap<String, u32>);
+
+/// A timed effect on an entity, e.g. a poison or a haste.
+#[derive(Debug, Clone)]
+pub struct StatusEffect {
+    pub name: String,
+    pub timer: Timer,
+    /// Dealt by the entity every second while the effect lasts.
+    pub damage: Option<(Entity, Damage)>,
+}
+
+#[derive(Debug, Default)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+impl StatusEffects {
+    /// Adds `effect`, renewing an effect of the same name instead of stacking it.
+    pub fn apply(&mut self, effect: StatusEffect) {
+        self.0.retain(|active| active.name != effect.name);
+        self.0.push(effect);
+    }
+}
+
+/// Debug flag: entities with it take no damage.
+#[derive(Debug)]
+pub struct GodMode;
+
+/// How a player moves on its own while a target is locked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FightMode {
+    Stand,
+    Chase,
+    KeepDistance,
+}
+
+impl Default for FightMode {
+    fn default() -> Self {
+        FightMode::Stand
+    }
+}
+
+impl FightMode {
+    pub fn next(self) -> Self {
+        match self {
+            FightMode::Stand => FightMode::Chase,
+            FightMode::Chase => FightMode::KeepDistance,
+            FightMode::KeepDistance => FightMode::Stand,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FightMode::Stand => "Stand",
+            FightMode::Chase => "Chase",
+            FightMode::KeepDistance => "Keep distance",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum DamageType {
     Physical,
     Fire,
@@ -68,19 +178,31 @@ pub enum DamageType {
     ManaDrain,
 }
 
-#[derive(Debug, Clone, Copy)]
+pub const DAMAGE_TYPES: [DamageType; 9] = [
+    DamageType::Physical,
+    DamageType::Fire,
+    DamageType::Water,
+    DamageType::Air,
+    DamageType::Earth,
+    DamageType::Holy,
+    DamageType::Death,
+    DamageType::LifeDrain,
+    DamageType::ManaDrain,
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Damage {
     pub value: f32,
     pub dtype: DamageType,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Health {
     pub max_value: f32,
     pub value: f32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mana {
     pub max_value: f32,
     pub value: f32,
@@ -92,6 +214,8 @@ pub(crate) struct Combat {
     pub mana: Mana,
     pub attack: Attack,
     pub defense: Defense,
+    pub status_effects: StatusEffects,
+    pub damage_taken: DamageTaken,
 }
 
 impl Default for Combat {
@@ -107,14 +231,16 @@ impl Default for Combat {
             },
             attack: Attack::default(),
             defense: Defense::default(),
+            status_effects: StatusEffects::default(),
+            damage_taken: DamageTaken::default(),
         }
     }
 }
 
 #[derive(Debug)]
 pub struct Defense {
-    value: f32,
-    rate: f32,
+    pub value: f32,
+    pub rate: f32,
 }
 
 impl Default for Defense {
@@ -150,6 +276,13 @@ impl Default for Attack {
     }
 }
 
+impl Attack {
+    /// Reach in world units, that of the `ranged` weapon when one is held.
+    pub fn reach(&self, ranged: Option<RangedWeapon>, tile_size: f32) -> f32 {
+        ranged.map_or(self.range, |weapon| weapon.range * tile_size)
+    }
+}
+
 #[derive(Debug)]
 struct AttackEvent {
     attacker: Entity,
@@ -161,10 +294,10 @@ struct MissEvent {
     defender: Entity,
 }
 #[derive(Debug)]
-struct ResistanceEvent {
-    attacker: Entity,
-    defender: Entity,
-    damage: DamageSet,
+pub(crate) struct ResistanceEvent {
+    pub(crate) attacker: Entity,
+    pub(crate) defender: Entity,
+    pub(crate) damage: DamageSet,
 }
 struct BlockEvent {
     attacker: Entity,
@@ -172,10 +305,10 @@ struct BlockEvent {
     damage: DamageSet,
 }
 #[derive(Debug)]
-struct DamageEvent {
-    attacker: Entity,
-    defender: Entity,
-    damage: DamageSet,
+pub(crate) struct DamageEvent {
+    pub(crate) attacker: Entity,
+    pub(crate) defender: Entity,
+    pub(crate) damage: DamageSet,
 }
 
 #[derive(Debug)]
@@ -191,61 +324,173 @@ struct PassiveSpell;
 #[derive(Debug, Clone)]
 pub struct DamageSet(pub Vec<Damage>);
 
+fn tick_status_effects(
+    time: Res<Time>,
+    mut resistance_events: EventWriter<ResistanceEvent>,
+    mut query: Query<(Entity, &mut StatusEffects)>,
+) {
+    for (defender, mut effects) in query.iter_mut() {
+        if effects.0.is_empty() {
+            continue;
+        }
+        for effect in effects.0.iter_mut() {
+            let seconds = effect.timer.elapsed_secs().floor();
+            effect.timer.tick(time.delta());
+            if let Some((attacker, damage)) = effect.damage {
+                for _ in 0..(effect.timer.elapsed_secs().floor() - seconds) as u32 {
+                    resistance_events.send(ResistanceEvent {
+                        attacker,
+                        defender,
+                        damage: DamageSet(vec![damage]),
+                    });
+                }
+            }
+        }
+        effects.0.retain(|effect| !effect.timer.finished());
+    }
+}
+
 fn passive_trigger() {
     // TODO: Thorns, Critical Strike, Bleeding, Burning, Poison, Etc.
 }
 
+/// Players swing at their target when it is in range and the attack interval has passed,
+/// training the weapon skill whether they hit or not. Ranged weapons need a clear line of sight
+/// and ammunition, and hit or miss once their projectile arrives.
 fn attack_system(
-    target: ResMut<LockedTarget>,
-    player: ResMut<LocalPlayer>,
-    transforms: Query<&Transform>,
-    mut attacks: Query<&mut Attack>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    tile_map: Res<TileMap>,
+    players: Query<(Entity, &LockedTarget), With<Player>>,
+    tiles: Query<&TilePos>,
+    mut attacks: Query<(
+        &mut Attack,
+        Option<&mut Equipments>,
+        Option<&Skills>,
+        &Transform,
+    )>,
     mut hit_events: EventWriter<AttackEvent>,
     mut miss_events: EventWriter<MissEvent>,
+    mut skill_tries: EventWriter<SkillTryEvent>,
+    mut messages: EventWriter<PlayerMessage>,
+    out_of_ammunition: Query<(), With<OutOfAmmunition>>,
 ) {
-    if let Ok(mut attack_attack) = attacks.get_mut(player.0) {
-        let attacker_range = attack_attack.range;
-        if let Some(t) = target.0 {
-            let t1 = transforms.get(player.0);
-            let t2 = transforms.get(t);
-            if let (Ok(t1), Ok(t2)) = (t1, t2) {
-                if t1.translation.distance(t2.translation) < attacker_range {
-                    if attack_attack.interval.finished() {
-                        let chance = thread_rng().gen_range(0.0..=100.);
-                        if attack_attack.rate > chance {
-                            hit_events.send(AttackEvent {
-                                attacker: player.0,
-                                defender: t,
-                            });
-                        } else {
-                            miss_events.send(MissEvent {
-                                attacker: player.0,
-                                defender: t,
-                            });
-                        }
-                        attack_attack.interval.reset();
-                    }
-                }
+    for (attacker, target) in players.iter() {
+        let defender = match target.0 {
+            Some(defender) => defender,
+            None => continue,
+        };
+        let (mut attack, equipments, skills, transform) = match attacks.get_mut(attacker) {
+            Ok(attack) => attack,
+            Err(_) => continue,
+        };
+        let (t1, t2) = match (tiles.get(attacker), tiles.get(defender)) {
+            (Ok(t1), Ok(t2)) => (*t1, *t2),
+            _ => continue,
+        };
+        let ranged = equipments.as_ref().and_then(|e| e.ranged_weapon());
+        let range = attack.reach(ranged, tile_map.tile_size);
+        if t1.world_distance(t2, tile_map.tile_size) >= range || !attack.interval.finished() {
+            continue;
+        }
+        if ranged.is_some() && !has_line_of_sight(&tile_map, t1, t2) {
+            continue;
+        }
+        let told = out_of_ammunition.get(attacker).is_ok();
+        if !equipments.as_ref().map_or(true, |e| e.has_ammunition()) {
+            if !told {
+                messages.send(PlayerMessage(
+                    attacker,
+                    GameMessage::error("You have no ammunition."),
+                ));
+                commands.entity(attacker).insert(OutOfAmmunition);
             }
+            continue;
+        }
+        if told {
+            commands.entity(attacker).remove::<OutOfAmmunition>();
+        }
+        attack.interval.reset();
+
+        let skill = equipments
+            .as_ref()
+            .map_or(Skill::Fist, |equipments| weapon_skill(equipments));
+        let chance = thread_rng().gen_range(0.0..=100.);
+        let hit = hit_chance(attack.rate, weapon_level(skills, skill)) > chance;
+        match (ranged, equipments) {
+            (Some(_), Some(mut equipments)) => {
+                let damage = weapon_damage(&attack, &equipments, skills);
+                // Thrown weapons are their own ammunition and land by the target.
+                let thrown = match ranged {
+                    Some(RangedWeapon { ammo: None, .. }) => equipments
+                        .mainhand
+                        .clone()
+                        .map(|item| Item { count: 1, ..item }),
+                    _ => None,
+                };
+                equipments.use_ammunition();
+                spawn_projectile(
+                    &mut commands,
+                    &mut materials,
+                    transform.translation,
+                    tile_map.tile_size,
+                    Projectile {
+                        attacker,
+                        defender,
+                        damage: Some(damage).filter(|_| hit),
+                        thrown,
+                    },
+                );
+            }
+            _ if hit => hit_events.send(AttackEvent { attacker, defender }),
+            _ => miss_events.send(MissEvent { attacker, defender }),
+        }
+        // The magic level only grows with mana spent, not with wand attacks.
+        if skill != Skill::MagicLevel {
+            skill_tries.send(SkillTryEvent {
+                entity: attacker,
+                skill,
+                tries: 1,
+            });
         }
     }
 }
 
+/// Weapon damage plus the base attack and the ammunition it shoots, scaled by the weapon skill.
+fn weapon_damage(attack: &Attack, equipments: &Equipments, skills: Option<&Skills>) -> DamageSet {
+    let factor = weapon_damage_factor(weapon_level(skills, weapon_skill(equipments)));
+    let mut damage_set = equipments.get_damage_set();
+    let ammo = equipments
+        .ammo
+        .as_ref()
+        .filter(|item| item.ammo.is_some())
+        .filter(|item| equipments.ranged_weapon().map(|weapon| weapon.ammo) == Some(item.ammo));
+    for attribute in ammo.iter().flat_map(|item| item.attributes.iter()) {
+        if let AttributeType::Damage(dtype) = attribute.attribute_type {
+            for dmg in damage_set.0.iter_mut().filter(|dmg| dmg.dtype == dtype) {
+                dmg.value += attribute.value;
+            }
+        }
+    }
+    damage_set.0.iter_mut().for_each(|dmg| {
+        if dmg.dtype == DamageType::Physical {
+            dmg.value += attack.damage.value;
+        }
+        dmg.value *= factor;
+    });
+    damage_set
+}
+
 fn hit_system(
     mut hit_events: EventReader<AttackEvent>,
     mut resistance_events: EventWriter<ResistanceEvent>,
-    mut attacks: Query<(&mut Attack, Option<&Equipments>)>,
+    attacks: Query<(&Attack, Option<&Equipments>, Option<&Skills>)>,
 ) {
     for hit in hit_events.iter() {
-        let mut damage_set = DamageSet(vec![]);
-        if let Ok((attacker_attack, Some(attacker_equipment))) = attacks.get_mut(hit.attacker) {
-            damage_set = attacker_equipment.get_damage_set();
-            damage_set.0.iter_mut().for_each(|&mut mut dmg| {
-                if dmg.dtype == DamageType::Physical {
-                    dmg.value += attacker_attack.damage.value;
-                }
-            });
-        }
+        let damage_set = match attacks.get(hit.attacker) {
+            Ok((attack, Some(equipments), skills)) => weapon_damage(attack, equipments, skills),
+            _ => DamageSet(vec![]),
+        };
         resistance_events.send(ResistanceEvent {
             attacker: hit.attacker,
             defender: hit.defender,
@@ -254,12 +499,134 @@ fn hit_system(
     }
 }
 
+/// Set on a player told its ranged weapon has no ammunition, until it has some again.
+pub struct OutOfAmmunition;
+
+/// Shot by a ranged weapon, it hits or misses `defender` when it reaches it.
+pub struct Projectile {
+    pub attacker: Entity,
+    pub defender: Entity,
+    /// `None` for a miss.
+    pub damage: Option<DamageSet>,
+    /// Thrown weapon dropped on the ground where the projectile lands.
+    pub thrown: Option<Item>,
+}
+
+fn spawn_projectile(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    from: Vec3,
+    tile_size: f32,
+    projectile: Projectile,
+) {
+    commands
+        .spawn()
+        .insert(projectile)
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                size: Vec2::new(tile_size / 6., tile_size / 6.),
+                ..Default::default()
+            },
+            material: materials.add(Color::WHITE.into()),
+            transform: Transform::from_xyz(from.x, from.y, 5.),
+            ..Default::default()
+        });
+}
+
+/// Flies projectiles towards their target, dropping those whose target is gone. Thrown weapons
+/// fall on the tile where their projectile ends.
+fn move_projectiles(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
+    tile_map: Res<TileMap>,
+    mut projectiles: Query<(Entity, &Projectile, &mut Transform)>,
+    targets: Query<&Transform, Without<Projectile>>,
+    mut resistance_events: EventWriter<ResistanceEvent>,
+    mut miss_events: EventWriter<MissEvent>,
+) {
+    let size = tile_map.tile_size;
+    for (entity, projectile, mut transform) in projectiles.iter_mut() {
+        let target = targets.get(projectile.defender).map(|t| t.translation);
+        let landed = match target {
+            Ok(target) => {
+                let to_target = (target - transform.translation).truncate();
+                let step = PROJECTILE_SPEED * size * time.delta_seconds();
+                if to_target.length() > step {
+                    transform.translation += (to_target.normalize() * step).extend(0.);
+                    continue;
+                }
+                target
+            }
+            Err(_) => transform.translation,
+        };
+        commands.entity(entity).despawn();
+        if let Some(item) = projectile.thrown.clone() {
+            let tile = TilePos::from_world(landed, size);
+            spawn_ground_item(
+                &mut commands,
+                &mut materials,
+                item,
+                tile.to_world(size, 0.),
+                size,
+            );
+        }
+        if target.is_err() {
+            continue;
+        }
+        match &projectile.damage {
+            Some(damage) => resistance_events.send(ResistanceEvent {
+                attacker: projectile.attacker,
+                defender: projectile.defender,
+                damage: damage.clone(),
+            }),
+            None => miss_events.send(MissEvent {
+                attacker: projectile.attacker,
+                defender: projectile.defender,
+            }),
+        }
+    }
+}
+
+/// Combat log lines for the players involved, each from its own point of view.
+fn combat_messages(
+    attacker: Entity,
+    defender: Entity,
+    names: &Query<&Name>,
+    players: &Query<&Player>,
+    messages: &mut EventWriter<PlayerMessage>,
+    to_defender: impl FnOnce(&str) -> String,
+    from_attacker: impl FnOnce(&str) -> String,
+) {
+    let name_of = |entity| names.get(entity).map_or("something", |n| n.value.as_str());
+    if players.get(defender).is_ok() {
+        let text = to_defender(name_of(attacker));
+        messages.send(PlayerMessage(defender, GameMessage::combat(text)));
+    }
+    if players.get(attacker).is_ok() {
+        let text = from_attacker(name_of(defender));
+        messages.send(PlayerMessage(attacker, GameMessage::combat(text)));
+    }
+}
+
 fn miss_system(
     mut commands: Commands,
     mut miss_events: EventReader<MissEvent>,
     asset_server: Res<AssetServer>,
+    names: Query<&Name>,
+    players: Query<&Player>,
+    mut messages: EventWriter<PlayerMessage>,
 ) {
     for event in miss_events.iter() {
+        combat_messages(
+            event.attacker,
+            event.defender,
+            &names,
+            &players,
+            &mut messages,
+            |name| format!("{} missed you.", name),
+            |name| format!("You missed {}.", name),
+        );
         commands.entity(event.defender).with_children(|parent| {
             parent.spawn_bundle(CombatTextBundle {
                 text: Text::with_section(
@@ -288,7 +655,7 @@ fn resistance_system(
     for block in resistance_events.iter() {
         let mut damage_set = block.damage.clone();
         if let Ok(Some(defender_equipments)) = query.get_mut(block.defender) {
-            damage_set.0.iter_mut().for_each(|&mut mut dmg| {
+            damage_set.0.iter_mut().for_each(|dmg| {
                 let factor =
                     defender_equipments.get_attributes(AttributeType::Resistance(dmg.dtype)) / 100.;
                 dmg.value -= dmg.value * factor;
@@ -302,16 +669,31 @@ fn resistance_system(
     }
 }
 
+/// Blocks physical damage with the equipment and, when holding a shield, the shielding skill,
+/// which such hits train.
 fn block_system(
     mut block_events: EventReader<BlockEvent>,
     mut damage_events: EventWriter<DamageEvent>,
-    mut query: Query<Option<&Equipments>>,
+    mut query: Query<(Option<&Equipments>, Option<&Skills>)>,
+    mut skill_tries: EventWriter<SkillTryEvent>,
 ) {
     for resistance in block_events.iter() {
         let mut damage_set = resistance.damage.clone();
-        if let Ok(Some(defender_equipments)) = query.get_mut(resistance.defender) {
-            let total_block = defender_equipments.get_attributes(AttributeType::Block);
-            damage_set.0.iter_mut().for_each(|&mut mut dmg| {
+        if let Ok((Some(defender_equipments), skills)) = query.get_mut(resistance.defender) {
+            let mut total_block = defender_equipments.get_attributes(AttributeType::Block);
+            let physical = damage_set
+                .0
+                .iter()
+                .any(|dmg| dmg.dtype == DamageType::Physical && dmg.value > 0.);
+            if holds_shield(defender_equipments) && physical {
+                total_block += shield_block(skill_level(skills, Skill::Shielding));
+                skill_tries.send(SkillTryEvent {
+                    entity: resistance.defender,
+                    skill: Skill::Shielding,
+                    tries: 1,
+                });
+            }
+            damage_set.0.iter_mut().for_each(|dmg| {
                 if dmg.dtype == DamageType::Physical {
                     dmg.value -= total_block;
                     if dmg.value < 0. {
@@ -354,19 +736,58 @@ fn damage_color(damage: DamageSet) -> Color {
 fn damage_system(
     mut commands: Commands,
     mut damage_event: EventReader<DamageEvent>,
-    mut query: Query<&mut Health>,
+    mut query: Query<(&mut Health, Option<&mut DamageTaken>), Without<GodMode>>,
+    time: Res<Time>,
     asset_server: Res<AssetServer>,
     mut death_events: EventWriter<DeathEvent>,
+    names: Query<&Name>,
+    players: Query<&Player>,
+    mut messages: EventWriter<PlayerMessage>,
 ) {
     for dmg in damage_event.iter() {
         let damage_set = dmg.damage.clone();
-        if let Ok(mut health) = query.get_mut(dmg.defender) {
+        if let Ok((mut health, damage_taken)) = query.get_mut(dmg.defender) {
             let mut total_damage = 0.;
             for d in damage_set.0.iter() {
                 total_damage += d.value;
             }
+            if let Some(mut damage_taken) = damage_taken {
+                // Overkill doesn't count towards the experience share.
+                let dealt = total_damage.min(health.value).max(0.);
+                let contribution = damage_taken.0.entry(dmg.attacker).or_insert(Contribution {
+                    damage: 0.,
+                    last_hit: 0.,
+                });
+                contribution.damage += dealt;
+                contribution.last_hit = time.seconds_since_startup();
+            }
+            let was_alive = health.value > 0.;
             health.value -= total_damage;
-            if health.value <= 0. {
+            combat_messages(
+                dmg.attacker,
+                dmg.defender,
+                &names,
+                &players,
+                &mut messages,
+                |name| {
+                    format!(
+                        "You lose {:.0} hitpoints due to an attack by {}.",
+                        total_damage, name
+                    )
+                },
+                |name| format!("You deal {:.0} damage to {}.", total_damage, name),
+            );
+            // Later hits on a creature already dead this frame don't kill it again.
+            if was_alive && health.value <= 0. {
+                combat_messages(
+                    dmg.attacker,
+                    dmg.defender,
+                    &names,
+                    &players,
+                    &mut messages,
+                    |name| format!("You were killed by {}.", name),
+                    |name| format!("You killed {}.", name),
+                );
                 death_events.send(DeathEvent {
                     attacker: dmg.attacker,
                     defender: dmg.defender,
@@ -395,20 +816,138 @@ fn death_system(mut commands: Commands, query: Query<(Entity, &Health)>) {
     }
 }
 
+fn forget_damage(time: Res<Time>, mut query: Query<&mut DamageTaken>) {
+    let now = time.seconds_since_startup();
+    for mut damage_taken in query.iter_mut() {
+        if damage_taken
+            .0
+            .values()
+            .any(|contribution| now - contribution.last_hit > DAMAGE_MEMORY)
+        {
+            damage_taken
+                .0
+                .retain(|_, contribution| now - contribution.last_hit <= DAMAGE_MEMORY);
+        }
+    }
+}
+
+/// Splits the experience of dead creatures by damage dealt, each party counting as one. Each
+/// share is then scaled by the level difference between its receiver and the creature.
 fn death_drop(
-    mut queryset: QuerySet<(
-        Query<&ExperiencePoints>,
-        Query<(Entity, &mut CurrentExperience, &NextLevelExperience)>,
-    )>,
+    mut commands: Commands,
     mut events: EventReader<DeathEvent>,
+    curve: Res<LevelCurve>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    dead: Query<(
+        &ExperiencePoints,
+        &TilePos,
+        &Name,
+        Option<&Level>,
+        Option<&DamageTaken>,
+        Option<&Loot>,
+    )>,
+    members: Query<(Entity, &PartyMember)>,
+    tiles: Query<&TilePos>,
+    tile_map: Res<TileMap>,
+    mut experiences: Query<(&mut CurrentExperience, &Level)>,
+    mut credits: EventWriter<KillCreditEvent>,
+    mut messages: EventWriter<PlayerMessage>,
 ) {
     for event in events.iter() {
-        if let Ok(experience) = queryset.q0().get(event.defender) {
-            let drop_experience = experience.0.clone();
-            if let Ok((_, mut cur, _)) = queryset.q1_mut().get_mut(event.attacker) {
-                cur.0 += drop_experience;
+        let (experience, tile, name, level, damage_taken, loot) = match dead.get(event.defender) {
+            Ok(dead) => dead,
+            Err(_) => continue,
+        };
+        let mut contributions: Vec<(Entity, f32)> = damage_taken
+            .iter()
+            .flat_map(|damage_taken| damage_taken.0.iter())
+            .filter(|(_, contribution)| contribution.damage > 0.)
+            .map(|(attacker, contribution)| (*attacker, contribution.damage))
+            .collect();
+        if contributions.is_empty() {
+            contributions.push((event.attacker, 1.));
+        }
+        contributions.sort_by_key(|(attacker, _)| attacker.id());
+        let total: f32 = contributions.iter().map(|(_, damage)| damage).sum();
+
+        if let Some(loot) = loot {
+            let mut rng = thread_rng();
+            let mut dropped = vec![];
+            for (title, chance) in loot.0.iter() {
+                if rng.gen_range(0.0..100.) >= *chance {
+                    continue;
+                }
+                if let Some(item) = Item::new(title) {
+                    spawn_ground_item(
+                        &mut commands,
+                        &mut materials,
+                        item,
+                        tile.to_world(tile_map.tile_size, 0.),
+                        tile_map.tile_size,
+                    );
+                    dropped.push(title.clone());
+                }
+            }
+            if dropped.is_empty() {
+                dropped.push("nothing".to_string());
+            }
+            let text = format!("Loot of {}: {}.", name.value, dropped.join(", "));
+            for (attacker, _) in contributions.iter() {
+                messages.send(PlayerMessage(*attacker, GameMessage::loot(text.clone())));
+            }
+        }
+
+        // (party leader or solo attacker, an attacker of that team, damage of the team)
+        let mut teams: Vec<(Entity, Entity, f32)> = Vec::new();
+        for (attacker, damage) in contributions {
+            credits.send(KillCreditEvent {
+                player: attacker,
+                creature: name.value.clone(),
+            });
+            let team = members
+                .get(attacker)
+                .map_or(attacker, |(_, member)| member.leader);
+            match teams.iter_mut().find(|(t, _, _)| *t == team) {
+                Some((_, _, team_damage)) => *team_damage += damage,
+                None => teams.push((team, attacker, damage)),
             }
         }
+
+        for (_, attacker, damage) in teams {
+            let team_experience = (experience.0 as f32 * damage / total).round() as u32;
+            let receivers = experience_receivers(
+                attacker,
+                *tile,
+                damage_taken,
+                &members,
+                &tiles,
+                tile_map.tile_size,
+            );
+            let share = shared_experience(team_experience, receivers.len());
+            let shared = receivers.len() > 1;
+            for receiver in receivers {
+                if let Ok((mut current, receiver_level)) = experiences.get_mut(receiver) {
+                    let monster_level = level.map_or(receiver_level.0, |level| level.0);
+                    let scale = curve.experience_scale(receiver_level.0, monster_level);
+                    let gained = (share as f32 * scale).round() as u32;
+                    current.0 += gained;
+                    let text = if shared {
+                        format!("You gained {} experience points with your party.", gained)
+                    } else {
+                        format!("You gained {} experience points.", gained)
+                    };
+                    messages.send(PlayerMessage(receiver, GameMessage::combat(text)));
+                }
+            }
+        }
+    }
+}
+
+fn record_kills(mut credits: EventReader<KillCreditEvent>, mut bestiaries: Query<&mut Bestiary>) {
+    for credit in credits.iter() {
+        if let Ok(mut bestiary) = bestiaries.get_mut(credit.player) {
+            *bestiary.0.entry(credit.creature.clone()).or_insert(0) += 1;
+        }
     }
 }
 
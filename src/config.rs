@@ -1,3 +1,34 @@
-pub(crate) const TILE_SIZE: f32 = 32.;
-pub(crate) const WIDTH: f32 = 800.;
-pub(crate) const HEIGHT: f32 = 600.;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, io, path::Path};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Ron(ron::Error),
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<ron::Error> for ConfigError {
+    fn from(error: ron::Error) -> Self {
+        ConfigError::Ron(error)
+    }
+}
+
+pub fn read_ron<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let contents = fs::read_to_string(path)?;
+    Ok(ron::de::from_str(&contents)?)
+}
+
+pub fn write_ron<T: Serialize>(path: &Path, value: &T) -> Result<(), ConfigError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::new())?;
+    fs::write(path, contents)?;
+    Ok(())
+}
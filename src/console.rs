@@ -0,0 +1,638 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use bevy::prelude::*;
+
+use crate::{
+    ai::SpawnMonsterEvent,
+    combat::{Damage, DamageEvent, DamageSet, DamageType, GodMode, Health, LockedTarget, Mana},
+    entities::{CurrentExperience, Level, Speed},
+    input::{slot_player, MoveAnimation, PlayerInput, KEYBOARD_SLOT},
+    input_map::{Action, Actions, TextInputFocus},
+    inventory::add_item,
+    item::{Capacity, Equipments, Item},
+    levels::LevelCurve,
+    map::{TileMap, TilePos},
+};
+
+/// Lines kept in the console window.
+const CONSOLE_LINES: usize = 12;
+
+/// Command registry, parser and the debug commands. Has no UI so it runs headless.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ConsoleRegistry>()
+            .add_event::<ConsoleCommandEvent>()
+            .add_event::<ConsoleCommand>()
+            .add_event::<ConsoleOutput>()
+            .add_system(dispatch_commands.system().label("console"))
+            .add_console_command("help", "/help", "Lists the commands.")
+            .add_console_command("spawn", "/spawn <monster> [x y]", "Spawns a monster.")
+            .add_console_command(
+                "give",
+                "/give <item> [count]",
+                "Adds items to the inventory.",
+            )
+            .add_console_command("tp", "/tp <x> <y>", "Teleports to a tile.")
+            .add_console_command("heal", "/heal", "Restores health and mana.")
+            .add_console_command("kill", "/kill", "Kills the locked target.")
+            .add_console_command("level", "/level <n>", "Sets the level.")
+            .add_console_command("god", "/god", "Toggles invulnerability.")
+            .add_console_command("speed", "/speed <n>", "Sets the movement speed.")
+            .add_system(help_command.system().after("console"))
+            .add_system(spawn_command.system().after("console"))
+            .add_system(give_command.system().after("console"))
+            .add_system(tp_command.system().after("console"))
+            .add_system(heal_command.system().after("console"))
+            .add_system(kill_command.system().after("console"))
+            .add_system(level_command.system().after("console"))
+            .add_system(god_command.system().after("console"))
+            .add_system(speed_command.system().after("console"));
+    }
+}
+
+/// Toggleable console window feeding `ConsoleCommandEvent`s.
+pub struct ConsoleUiPlugin;
+
+impl Plugin for ConsoleUiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ConsoleState>()
+            .add_startup_system(setup.system())
+            .add_system(toggle_console.system().after("actions"))
+            .add_system(console_typing.system())
+            .add_system(console_history.system().after("console"))
+            .add_system(console_text.system());
+    }
+}
+
+pub trait ConsoleAppExt {
+    fn add_console_command(&mut self, name: &str, usage: &str, help: &str) -> &mut Self;
+}
+
+impl ConsoleAppExt for AppBuilder {
+    fn add_console_command(&mut self, name: &str, usage: &str, help: &str) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(ConsoleRegistry::default)
+            .commands
+            .insert(
+                name.to_string(),
+                ConsoleCommandInfo {
+                    usage: usage.to_string(),
+                    help: help.to_string(),
+                },
+            );
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsoleCommandInfo {
+    pub usage: String,
+    pub help: String,
+}
+
+/// Known commands by name. Plugins add theirs with `add_console_command` and handle the
+/// matching `ConsoleCommand` events.
+#[derive(Debug, Default)]
+pub struct ConsoleRegistry {
+    pub commands: BTreeMap<String, ConsoleCommandInfo>,
+}
+
+/// A raw line such as `/tp 3 4`.
+#[derive(Debug)]
+pub struct ConsoleCommandEvent(pub String);
+
+/// A parsed line whose name is in the `ConsoleRegistry`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleCommand {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ConsoleOutput(pub String);
+
+/// Splits `/name arg arg` into the name and its arguments. Double quotes keep spaces in an
+/// argument, e.g. `/give "iron sword" 2`.
+pub fn parse_command(line: &str) -> Result<ConsoleCommand, String> {
+    let line = line.trim();
+    if !line.starts_with('/') {
+        return Err("Commands start with '/'.".to_string());
+    }
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    for c in line[1..].chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err("Unclosed quote.".to_string());
+    }
+    words.extend(word);
+    let mut words = words.into_iter();
+    match words.next() {
+        Some(name) if !name.is_empty() => Ok(ConsoleCommand {
+            name: name.to_lowercase(),
+            args: words.collect(),
+        }),
+        _ => Err("Empty command.".to_string()),
+    }
+}
+
+fn parse_arg<T: FromStr>(args: &[String], i: usize, usage: &str) -> Result<T, String> {
+    args.get(i)
+        .and_then(|arg| arg.parse().ok())
+        .ok_or_else(|| format!("Usage: {}", usage))
+}
+
+fn dispatch_commands(
+    registry: Res<ConsoleRegistry>,
+    mut events: EventReader<ConsoleCommandEvent>,
+    mut console_commands: EventWriter<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+) {
+    for event in events.iter() {
+        output.send(ConsoleOutput(event.0.clone()));
+        match parse_command(&event.0) {
+            Ok(command) if registry.commands.contains_key(&command.name) => {
+                console_commands.send(command)
+            }
+            Ok(command) => output.send(ConsoleOutput(format!(
+                "Unknown command '{}', try /help.",
+                command.name
+            ))),
+            Err(e) => output.send(ConsoleOutput(e)),
+        }
+    }
+}
+
+fn help_command(
+    registry: Res<ConsoleRegistry>,
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+) {
+    for _ in console_commands.iter().filter(|c| c.name == "help") {
+        for info in registry.commands.values() {
+            output.send(ConsoleOutput(format!("{} - {}", info.usage, info.help)));
+        }
+    }
+}
+
+fn spawn_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    mut spawn_events: EventWriter<SpawnMonsterEvent>,
+    players: Query<(Entity, &PlayerInput)>,
+    tiles: Query<&TilePos>,
+) {
+    for command in console_commands.iter().filter(|c| c.name == "spawn") {
+        let usage = "/spawn <monster> [x y]";
+        let name = match command.args.first() {
+            Some(name) => name.clone(),
+            None => {
+                output.send(ConsoleOutput(format!("Usage: {}", usage)));
+                continue;
+            }
+        };
+        let tile = if command.args.len() >= 3 {
+            match (
+                parse_arg(&command.args, 1, usage),
+                parse_arg(&command.args, 2, usage),
+            ) {
+                (Ok(x), Ok(y)) => TilePos::new(x, y),
+                (Err(e), _) | (_, Err(e)) => {
+                    output.send(ConsoleOutput(e));
+                    continue;
+                }
+            }
+        } else {
+            match slot_player(&players, KEYBOARD_SLOT).map(|player| tiles.get(player)) {
+                Some(Ok(tile)) => tile.offset(IVec2::new(1, 0)),
+                _ => continue,
+            }
+        };
+        output.send(ConsoleOutput(format!(
+            "Spawned {} at {} {}.",
+            name, tile.x, tile.y
+        )));
+        spawn_events.send(SpawnMonsterEvent { name, tile });
+    }
+}
+
+fn give_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    players: Query<(Entity, &PlayerInput)>,
+    mut inventories: Query<(&Capacity, &Equipments, &mut Vec<Item>)>,
+) {
+    for command in console_commands.iter().filter(|c| c.name == "give") {
+        let mut words = command.args.clone();
+        let count = match words.last().and_then(|w| w.parse::<usize>().ok()) {
+            Some(count) if words.len() > 1 => {
+                words.pop();
+                count
+            }
+            _ => 1,
+        };
+        let title = words.join(" ");
+        let item = match Item::new(&title) {
+            Some(item) => item,
+            None => {
+                output.send(ConsoleOutput(format!("Unknown item '{}'.", title)));
+                continue;
+            }
+        };
+        if let Some(Ok((capacity, equipments, mut inventory))) =
+            slot_player(&players, KEYBOARD_SLOT).map(|player| inventories.get_mut(player))
+        {
+            let mut given = 0;
+            for _ in 0..count {
+                if let Err(e) = add_item(item.clone(), *capacity, equipments, &mut inventory) {
+                    output.send(ConsoleOutput(e));
+                    break;
+                }
+                given += 1;
+            }
+            output.send(ConsoleOutput(format!("Gave {} {}.", given, title)));
+        }
+    }
+}
+
+fn tp_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    mut commands: Commands,
+    players: Query<(Entity, &PlayerInput)>,
+    tile_map: Res<TileMap>,
+    mut query: Query<(&mut TilePos, &mut Transform)>,
+) {
+    for command in console_commands.iter().filter(|c| c.name == "tp") {
+        let usage = "/tp <x> <y>";
+        let tile = match (
+            parse_arg(&command.args, 0, usage),
+            parse_arg(&command.args, 1, usage),
+        ) {
+            (Ok(x), Ok(y)) => TilePos::new(x, y),
+            (Err(e), _) | (_, Err(e)) => {
+                output.send(ConsoleOutput(e));
+                continue;
+            }
+        };
+        let player = match slot_player(&players, KEYBOARD_SLOT) {
+            Some(player) => player,
+            None => continue,
+        };
+        if let Ok((mut player_tile, mut transform)) = query.get_mut(player) {
+            *player_tile = tile;
+            transform.translation = tile.to_world(tile_map.tile_size, transform.translation.z);
+            commands.entity(player).remove::<MoveAnimation>();
+            output.send(ConsoleOutput(format!(
+                "Teleported to {} {}.",
+                tile.x, tile.y
+            )));
+        }
+    }
+}
+
+fn heal_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    players: Query<(Entity, &PlayerInput)>,
+    mut query: Query<(&mut Health, &mut Mana)>,
+) {
+    for _ in console_commands.iter().filter(|c| c.name == "heal") {
+        if let Some(Ok((mut health, mut mana))) =
+            slot_player(&players, KEYBOARD_SLOT).map(|player| query.get_mut(player))
+        {
+            health.value = health.max_value;
+            mana.value = mana.max_value;
+            output.send(ConsoleOutput("Healed.".to_string()));
+        }
+    }
+}
+
+fn kill_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    mut damage_events: EventWriter<DamageEvent>,
+    players: Query<(Entity, &PlayerInput)>,
+    targets: Query<&LockedTarget>,
+    query: Query<&Health>,
+) {
+    for _ in console_commands.iter().filter(|c| c.name == "kill") {
+        let player = match slot_player(&players, KEYBOARD_SLOT) {
+            Some(player) => player,
+            None => continue,
+        };
+        let target = match targets.get(player).ok().and_then(|target| target.0) {
+            Some(target) => target,
+            None => {
+                output.send(ConsoleOutput("You need a target.".to_string()));
+                continue;
+            }
+        };
+        if let Ok(health) = query.get(target) {
+            damage_events.send(DamageEvent {
+                attacker: player,
+                defender: target,
+                damage: DamageSet(vec![Damage {
+                    value: health.value,
+                    dtype: DamageType::Physical,
+                }]),
+            });
+        }
+    }
+}
+
+fn level_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    players: Query<(Entity, &PlayerInput)>,
+    curve: Res<LevelCurve>,
+    mut query: Query<(&mut Level, &mut CurrentExperience)>,
+) {
+    for command in console_commands.iter().filter(|c| c.name == "level") {
+        let level = match parse_arg::<u32>(&command.args, 0, "/level <n>") {
+            Ok(level) => level,
+            Err(e) => {
+                output.send(ConsoleOutput(e));
+                continue;
+            }
+        };
+        let level = level.min(curve.max_level);
+        if let Some(Ok((mut lvl, mut cur))) =
+            slot_player(&players, KEYBOARD_SLOT).map(|player| query.get_mut(player))
+        {
+            lvl.0 = level;
+            cur.0 = 0;
+            output.send(ConsoleOutput(format!("Level set to {}.", level)));
+        }
+    }
+}
+
+fn god_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    mut commands: Commands,
+    players: Query<(Entity, &PlayerInput)>,
+    query: Query<(), With<GodMode>>,
+) {
+    for _ in console_commands.iter().filter(|c| c.name == "god") {
+        let player = match slot_player(&players, KEYBOARD_SLOT) {
+            Some(player) => player,
+            None => continue,
+        };
+        if query.get(player).is_ok() {
+            commands.entity(player).remove::<GodMode>();
+            output.send(ConsoleOutput("God mode off.".to_string()));
+        } else {
+            commands.entity(player).insert(GodMode);
+            output.send(ConsoleOutput("God mode on.".to_string()));
+        }
+    }
+}
+
+fn speed_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    players: Query<(Entity, &PlayerInput)>,
+    mut query: Query<&mut Speed>,
+) {
+    for command in console_commands.iter().filter(|c| c.name == "speed") {
+        let value = match parse_arg::<f32>(&command.args, 0, "/speed <n>") {
+            Ok(value) => value,
+            Err(e) => {
+                output.send(ConsoleOutput(e));
+                continue;
+            }
+        };
+        if let Some(Ok(mut speed)) =
+            slot_player(&players, KEYBOARD_SLOT).map(|player| query.get_mut(player))
+        {
+            // The step interval is `base_interval - value`, keep it positive.
+            speed.value = value.max(0.).min(speed.base_interval - 50.);
+            output.send(ConsoleOutput(format!("Speed set to {}.", speed.value)));
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ConsoleState {
+    pub open: bool,
+    pub input: String,
+    pub history: Vec<String>,
+}
+
+pub struct ConsoleWindow;
+pub struct ConsoleText;
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(25.),
+                    left: Val::Px(5.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(500.), Val::Px(200.)),
+                align_items: AlignItems::FlexEnd,
+                padding: Rect::all(Val::Px(4.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0., 0., 0., 0.8).into()),
+            ..Default::default()
+        })
+        .insert(ConsoleWindow)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("fonts/font.ttf"),
+                            font_size: 12.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(ConsoleText);
+        });
+}
+
+fn toggle_console(
+    actions: Res<Actions>,
+    mut state: ResMut<ConsoleState>,
+    mut focus: ResMut<TextInputFocus>,
+    mut windows: Query<&mut Style, With<ConsoleWindow>>,
+) {
+    if !actions.just_pressed(Action::ToggleConsole) {
+        return;
+    }
+    state.open = !state.open;
+    focus.0 = state.open;
+    for mut style in windows.iter_mut() {
+        style.display = if state.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+fn console_typing(
+    mut state: ResMut<ConsoleState>,
+    mut characters: EventReader<ReceivedCharacter>,
+    keyboard_inputs: Res<Input<KeyCode>>,
+    mut command_events: EventWriter<ConsoleCommandEvent>,
+) {
+    for event in characters.iter() {
+        // The toggle key itself and control characters like backspace are not text.
+        if state.open && !event.char.is_control() && event.char != '`' && event.char != '~' {
+            state.input.push(event.char);
+        }
+    }
+    if !state.open {
+        return;
+    }
+    if keyboard_inputs.just_pressed(KeyCode::Back) {
+        state.input.pop();
+    }
+    if keyboard_inputs.just_pressed(KeyCode::Return) && !state.input.trim().is_empty() {
+        let line = std::mem::take(&mut state.input);
+        command_events.send(ConsoleCommandEvent(line));
+    }
+}
+
+fn console_history(mut state: ResMut<ConsoleState>, mut output: EventReader<ConsoleOutput>) {
+    for line in output.iter() {
+        state.history.push(line.0.clone());
+    }
+    let len = state.history.len();
+    if len > CONSOLE_LINES {
+        state.history.drain(..len - CONSOLE_LINES);
+    }
+}
+
+fn console_text(state: Res<ConsoleState>, mut texts: Query<&mut Text, With<ConsoleText>>) {
+    if !state.is_changed() {
+        return;
+    }
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!("{}\n> {}_", state.history.join("\n"), state.input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::add_test_plugins;
+    use bevy::app::Events;
+
+    fn command(name: &str, args: &[&str]) -> ConsoleCommand {
+        ConsoleCommand {
+            name: name.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn parse_words() {
+        assert_eq!(parse_command("/tp 3 -4"), Ok(command("tp", &["3", "-4"])));
+        assert_eq!(parse_command("  /HEAL  "), Ok(command("heal", &[])));
+        assert!(parse_command("tp 3 4").is_err());
+    }
+
+    #[test]
+    fn parse_quotes() {
+        assert_eq!(
+            parse_command("/give \"iron  sword\" 2"),
+            Ok(command("give", &["iron  sword", "2"]))
+        );
+        assert_eq!(
+            parse_command("/spawn \"\" 1 2"),
+            Ok(command("spawn", &["", "1", "2"]))
+        );
+        assert!(parse_command("/give \"iron sword").is_err());
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert!(parse_command("/").is_err());
+        assert!(parse_command("/   ").is_err());
+        assert!(parse_command("").is_err());
+    }
+
+    /// The console alone, with the events and resources its commands use.
+    fn console_app() -> App {
+        let mut builder = App::build();
+        add_test_plugins(&mut builder);
+        builder
+            .add_event::<SpawnMonsterEvent>()
+            .add_event::<DamageEvent>()
+            .init_resource::<LevelCurve>()
+            .init_resource::<TileMap>()
+            .add_plugin(ConsolePlugin);
+        builder.app
+    }
+
+    fn run(app: &mut App, line: &str) -> Vec<String> {
+        app.world
+            .get_resource_mut::<Events<ConsoleCommandEvent>>()
+            .unwrap()
+            .send(ConsoleCommandEvent(line.to_string()));
+        app.update();
+        let events = app.world.get_resource::<Events<ConsoleOutput>>().unwrap();
+        events
+            .get_reader()
+            .iter(events)
+            .map(|output| output.0.clone())
+            .collect()
+    }
+
+    #[test]
+    fn unknown_command() {
+        let mut app = console_app();
+        let output = run(&mut app, "/fly 3");
+        assert_eq!(output, vec!["/fly 3", "Unknown command 'fly', try /help."]);
+    }
+
+    #[test]
+    fn heal_restores_the_player() {
+        let mut app = console_app();
+        let player = app
+            .world
+            .spawn()
+            .insert(PlayerInput(KEYBOARD_SLOT))
+            .insert(Health {
+                max_value: 100.,
+                value: 10.,
+            })
+            .insert(Mana {
+                max_value: 50.,
+                value: 0.,
+            })
+            .id();
+
+        let output = run(&mut app, "/heal");
+        assert!(output.contains(&"Healed.".to_string()));
+        assert_eq!(app.world.get::<Health>(player).unwrap().value, 100.);
+        assert_eq!(app.world.get::<Mana>(player).unwrap().value, 50.);
+    }
+}
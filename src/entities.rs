@@ -1,21 +1,66 @@
-use crate::combat::{create_combat_text, Combat, CombatText, Health};
-use crate::config::TILE_SIZE;
-use crate::{item::*, Bars, HealthManaBar, HealthManaBarBundle};
+use crate::combat::{
+    create_combat_text, Bestiary, Combat, CombatText, FightMode, Health, LockedTarget, Mana,
+};
+use crate::config::{read_ron, write_ron};
+use crate::console::{ConsoleAppExt, ConsoleCommand, ConsoleOutput};
+use crate::hotbar::{Cooldowns, Hotbar};
+use crate::input::PlayerInput;
+use crate::levels::{LevelCurve, LEVEL_CURVE_PATH};
+use crate::map::{ExploredTiles, TileMap, TilePos};
+use crate::messages::GameMessage;
+use crate::settings::Settings;
+use crate::skills::Skills;
+use crate::states::AppState;
+use crate::visibility::{is_seen, VisibleTiles};
+use crate::vocation::Vocation;
+use crate::{item::*, Bars, HealthBar, HealthManaBar, HealthManaBarBundle, LocalPlayer, ManaBar};
 use bevy::core::Timer;
+use bevy::ecs::component::Component;
 use bevy::prelude::*;
+use bevy::render::{camera::ActiveCameras, render_graph::base::camera::CAMERA_2D};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::time::Duration;
 
+pub const BARS_CONFIG_PATH: &str = "config/bars.ron";
+
 pub struct EntityPlugin;
 
 impl Plugin for EntityPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(healthbar_change.system())
-            .add_system(insert_entity_name.system())
-            .add_system(insert_healthbar.system())
-            .add_system(insert_entity_combat.system())
-            .add_system(exp_change.system())
-            .add_system(level_up.system())
-            .add_system(combat_text.system());
+        app.insert_resource(LevelCurve::load(Path::new(LEVEL_CURVE_PATH)))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(exp_change.system())
+                    .with_system(combat_text.system()),
+            );
+    }
+}
+
+/// Bars, names, level ups and the camera of the entities this machine shows.
+pub struct EntityUiPlugin;
+
+impl Plugin for EntityUiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(BarVisibility::load(Path::new(BARS_CONFIG_PATH)))
+            .add_console_command(
+                "bars",
+                "/bars <always|damaged|target>",
+                "Sets when health and mana bars are shown.",
+            )
+            .add_system(bars_command.system().after("console"))
+            .add_system(save_bar_visibility.system())
+            .add_system(activate_player_camera.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(healthbar_change.system())
+                    .with_system(manabar_change.system())
+                    .with_system(bar_visibility.system())
+                    .with_system(insert_entity_name.system())
+                    .with_system(insert_healthbar.system())
+                    .with_system(insert_entity_combat.system())
+                    .with_system(level_up.system()),
+            );
     }
 }
 
@@ -44,27 +89,44 @@ impl Default for Levelling {
 }
 
 fn exp_change(
+    curve: Res<LevelCurve>,
     mut query: Query<
         (&mut CurrentExperience, &mut NextLevelExperience, &mut Level),
-        Changed<CurrentExperience>,
+        Or<(Changed<CurrentExperience>, Changed<Level>)>,
     >,
 ) {
     for (mut cur, mut next, mut lvl) in query.iter_mut() {
-        if cur.0 >= next.0 {
-            cur.0 -= next.0;
-            lvl.0 += 1;
-            next.0 = lvl.0 * (((lvl.0 * 100) as f32 * 1.2) as u32);
-            println!("{:?}", (lvl, cur, next));
+        let (level, experience) = curve.advance(lvl.0, cur.0);
+        let needed = curve.experience_for(level).unwrap_or(0);
+        if lvl.0 != level {
+            lvl.0 = level;
+        }
+        if cur.0 != experience {
+            cur.0 = experience;
+        }
+        if next.0 != needed {
+            next.0 = needed;
         }
     }
 }
 
 fn level_up(
     mut commands: Commands,
-    query: Query<Entity, Changed<Level>>,
+    query: Query<(Entity, &Level, ChangeTrackers<Level>)>,
     asset_server: Res<AssetServer>,
+    player: Res<LocalPlayer>,
+    mut messages: EventWriter<GameMessage>,
 ) {
-    for entity in query.iter() {
+    for (entity, level, tracker) in query.iter() {
+        if !tracker.is_changed() || tracker.is_added() {
+            continue;
+        }
+        if player.0 == Some(entity) {
+            messages.send(GameMessage::system(format!(
+                "You advanced to level {}.",
+                level.0
+            )));
+        }
         create_combat_text(
             entity,
             "Level up!".to_string(),
@@ -78,16 +140,119 @@ fn level_up(
     }
 }
 
+/// When the health and mana bars above entities are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BarVisibility {
+    Always,
+    Damaged,
+    PlayerAndTarget,
+}
+
+impl Default for BarVisibility {
+    fn default() -> Self {
+        BarVisibility::Always
+    }
+}
+
+impl BarVisibility {
+    pub fn load(path: &Path) -> Self {
+        read_ron(path).unwrap_or_default()
+    }
+}
+
+fn bars_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    mut visibility: ResMut<BarVisibility>,
+) {
+    for command in console_commands.iter().filter(|c| c.name == "bars") {
+        *visibility = match command.args.first().map(|a| a.as_str()) {
+            Some("always") => BarVisibility::Always,
+            Some("damaged") => BarVisibility::Damaged,
+            Some("target") => BarVisibility::PlayerAndTarget,
+            _ => {
+                output.send(ConsoleOutput(
+                    "Usage: /bars <always|damaged|target>".to_string(),
+                ));
+                continue;
+            }
+        };
+        output.send(ConsoleOutput(format!("Bars: {:?}.", *visibility)));
+    }
+}
+
+fn save_bar_visibility(visibility: Res<BarVisibility>) {
+    if visibility.is_changed() {
+        if let Err(e) = write_ron(Path::new(BARS_CONFIG_PATH), &*visibility) {
+            println!("Failed to save bar visibility: {:?}", e);
+        }
+    }
+}
+
+/// Fills `percent` of a bar as wide as a tile, from the left.
+fn resize_bar(sprite: &mut Sprite, transform: &mut Transform, percent: f32, tile_size: f32) {
+    sprite.size = Vec2::new(tile_size * percent, sprite.size.y);
+    transform.translation.x = ((tile_size / 2.) * percent) - (tile_size / 2.);
+}
+
 fn healthbar_change(
+    tile_map: Res<TileMap>,
     query: Query<(&Health, &Children), Changed<Health>>,
-    mut bars: Query<(&mut Sprite, &mut Transform, &Bars)>,
+    mut bars: Query<(&mut Sprite, &mut Transform), With<HealthBar>>,
 ) {
     for (health, children) in query.iter() {
         for child in children.iter() {
-            if let Ok((mut sprite, mut transform, _)) = bars.get_mut(*child) {
+            if let Ok((mut sprite, mut transform)) = bars.get_mut(*child) {
                 let percent = health.value / health.max_value;
-                sprite.size = Vec2::new(TILE_SIZE * percent, sprite.size.y);
-                transform.translation.x = ((TILE_SIZE / 2.) * percent) - (TILE_SIZE / 2.);
+                resize_bar(&mut sprite, &mut transform, percent, tile_map.tile_size);
+            }
+        }
+    }
+}
+
+fn manabar_change(
+    tile_map: Res<TileMap>,
+    query: Query<(&Mana, &Children), Changed<Mana>>,
+    mut bars: Query<(&mut Sprite, &mut Transform), With<ManaBar>>,
+) {
+    for (mana, children) in query.iter() {
+        for child in children.iter() {
+            if let Ok((mut sprite, mut transform)) = bars.get_mut(*child) {
+                let percent = mana.value / mana.max_value;
+                resize_bar(&mut sprite, &mut transform, percent, tile_map.tile_size);
+            }
+        }
+    }
+}
+
+fn bar_visibility(
+    settings: Res<Settings>,
+    visibility: Res<BarVisibility>,
+    player: Res<LocalPlayer>,
+    visible_tiles: Res<VisibleTiles>,
+    targets: Query<&LockedTarget>,
+    query: Query<(Entity, &Health, &Children, Option<&TilePos>), With<HealthManaBar>>,
+    mut bars: Query<&mut Visible, With<Bars>>,
+) {
+    let target = player
+        .0
+        .and_then(|player| targets.get(player).ok())
+        .and_then(|target| target.0);
+    for (entity, health, children, tile) in query.iter() {
+        let visible = settings.show_health_bars
+            && is_seen(&player, &visible_tiles, tile)
+            && match *visibility {
+                BarVisibility::Always => true,
+                BarVisibility::Damaged => health.value < health.max_value,
+                BarVisibility::PlayerAndTarget => {
+                    player.0 == Some(entity) || target == Some(entity)
+                }
+            };
+        for child in children.iter() {
+            if let Ok(mut bar) = bars.get_mut(*child) {
+                if bar.is_visible != visible {
+                    bar.is_visible = visible;
+                }
             }
         }
     }
@@ -128,44 +293,87 @@ fn insert_entity_name(
 
 const HEALTH_MANA_BAR_POSITION: f32 = 14.;
 
+/// Black background and coloured fill of one bar, `offset` below the health bar.
+fn spawn_bar<T: Component>(
+    parent: &mut ChildBuilder,
+    materials: &mut Assets<ColorMaterial>,
+    color: Color,
+    offset: f32,
+    percent: f32,
+    tile_size: f32,
+    marker: T,
+) {
+    parent.spawn_bundle(HealthManaBarBundle {
+        sprite: Sprite {
+            size: Vec2::new(tile_size, 4.),
+            ..Default::default()
+        },
+        material: materials.add(Color::BLACK.into()),
+        transform: Transform::from_xyz(0., HEALTH_MANA_BAR_POSITION - offset, 6.),
+        ..Default::default()
+    });
+    let mut sprite = Sprite {
+        size: Vec2::new(tile_size, 3.),
+        ..Default::default()
+    };
+    let mut transform = Transform::from_xyz(0., HEALTH_MANA_BAR_POSITION - offset, 7.);
+    resize_bar(&mut sprite, &mut transform, percent, tile_size);
+    parent
+        .spawn_bundle(HealthManaBarBundle {
+            sprite,
+            material: materials.add(color.into()),
+            transform,
+            ..Default::default()
+        })
+        .insert(marker);
+}
+
 fn insert_healthbar(
     mut commands: Commands,
-    query: Query<Entity, Added<HealthManaBar>>,
+    tile_map: Res<TileMap>,
+    query: Query<(Entity, &Health, Option<&Mana>), Added<HealthManaBar>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    for entity in query.iter() {
-        commands
-            .entity(entity)
-            .with_children(|parent| {
-                parent.spawn_bundle(SpriteBundle {
-                    sprite: Sprite {
-                        size: Vec2::new(TILE_SIZE, 4.),
-                        ..Default::default()
-                    },
-                    material: materials.add(Color::BLACK.into()),
-                    transform: Transform::from_xyz(0., HEALTH_MANA_BAR_POSITION, 6.),
-                    ..Default::default()
-                });
-            })
-            .with_children(|parent| {
-                parent.spawn_bundle(HealthManaBarBundle {
-                    sprite: Sprite {
-                        size: Vec2::new(TILE_SIZE, 3.),
-                        ..Default::default()
-                    },
-                    material: materials.add(Color::GREEN.into()),
-                    transform: Transform::from_xyz(0., HEALTH_MANA_BAR_POSITION, 7.),
-                    ..Default::default()
-                });
-            });
+    let size = tile_map.tile_size;
+    for (entity, health, mana) in query.iter() {
+        commands.entity(entity).with_children(|parent| {
+            let percent = health.value / health.max_value;
+            spawn_bar(
+                parent,
+                &mut materials,
+                Color::GREEN,
+                0.,
+                percent,
+                size,
+                HealthBar,
+            );
+            if let Some(mana) = mana {
+                let percent = mana.value / mana.max_value;
+                spawn_bar(
+                    parent,
+                    &mut materials,
+                    Color::BLUE,
+                    4.,
+                    percent,
+                    size,
+                    ManaBar,
+                );
+            }
+        });
     }
 }
 
+/// Floats combat text up and away, shown when the settings of a game with a window allow it.
 fn combat_text(
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Transform, &Timer, &CombatText)>,
+    settings: Option<Res<Settings>>,
+    mut query: Query<(Entity, &mut Transform, &mut Visible, &Timer, &CombatText)>,
 ) {
-    for (entity, mut transform, timer, _) in query.iter_mut() {
+    let shown = settings.map_or(false, |settings| settings.combat_text);
+    for (entity, mut transform, mut visible, timer, _) in query.iter_mut() {
+        if visible.is_visible != shown {
+            visible.is_visible = shown;
+        }
         if timer.finished() {
             transform.translation.y += 0.6;
             if transform.translation.y > 20. {
@@ -181,13 +389,42 @@ pub struct Player;
 #[derive(Debug)]
 pub struct Body;
 
+/// The 2d camera following the player it belongs to, as opposed to the UI camera.
+pub struct PlayerCamera(pub Entity);
+
+/// Renders the world through the camera of the `LocalPlayer`.
+fn activate_player_camera(
+    player: Res<LocalPlayer>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    cameras: Query<(Entity, &PlayerCamera)>,
+) {
+    let camera = cameras
+        .iter()
+        .find(|(_, owner)| player.0 == Some(owner.0))
+        .map(|(camera, _)| camera);
+    if let (Some(camera), Some(active)) = (camera, active_cameras.get_mut(CAMERA_2D)) {
+        if active.entity != Some(camera) {
+            active.entity = Some(camera);
+        }
+    }
+}
+
 #[derive(Debug, Bundle)]
 pub struct PlayerComponents {
     player: Player,
     name: Name,
     speed: Speed,
+    vocation: Vocation,
+    skills: Skills,
+    target: LockedTarget,
+    fight_mode: FightMode,
+    hotbar: Hotbar,
+    cooldowns: Cooldowns,
     equipments: Equipments,
+    capacity: Capacity,
     inventory: Vec<Item>,
+    explored: ExploredTiles,
+    bestiary: Bestiary,
     body: Body,
 
     #[bundle]
@@ -197,22 +434,89 @@ pub struct PlayerComponents {
 }
 
 impl PlayerComponents {
-    pub fn new(name: &str) -> PlayerComponents {
+    pub fn new(name: &str, vocation: Vocation) -> PlayerComponents {
         let p = PlayerComponents {
             name: Name {
                 value: name.to_string(),
             },
-            equipments: Equipments {
-                mainhand: Item::new("Sword"),
-                offhand: Item::new("Wooden Shield"),
-                ..Default::default()
+            vocation,
+            equipments: match vocation {
+                Vocation::Knight => Equipments {
+                    mainhand: Item::new("Sword"),
+                    offhand: Item::new("Wooden Shield"),
+                    ..Default::default()
+                },
+                Vocation::Paladin => Equipments {
+                    mainhand: Item::new("Bow"),
+                    ammo: Item::new("Arrow").map(|arrow| Item { count: 50, ..arrow }),
+                    ..Default::default()
+                },
+                Vocation::Sorcerer => Equipments {
+                    mainhand: Item::new("Wand"),
+                    ..Default::default()
+                },
+                Vocation::Druid => Equipments {
+                    mainhand: Item::new("Rod"),
+                    ..Default::default()
+                },
             },
+            inventory: vec!["Health Potion", "Health Potion", "Mana Potion"]
+                .into_iter()
+                .filter_map(Item::new)
+                .collect(),
             ..Default::default()
         };
         p
     }
 }
 
+/// A player driven by this machine's input `slot`, seen through its own camera.
+pub fn spawn_player(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    name: &str,
+    vocation: Vocation,
+    translation: Vec3,
+    tile_size: f32,
+    slot: u8,
+) -> Entity {
+    let player = spawn_player_body(commands, materials, name, vocation, translation, tile_size);
+    commands
+        .entity(player)
+        .insert(PlayerInput(slot))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(OrthographicCameraBundle::new_2d())
+                .insert(PlayerCamera(player));
+        });
+    player
+}
+
+/// A player without a camera nor input, for players controlled elsewhere.
+pub fn spawn_player_body(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    name: &str,
+    vocation: Vocation,
+    translation: Vec3,
+    tile_size: f32,
+) -> Entity {
+    commands
+        .spawn()
+        .insert_bundle(PlayerComponents::new(name, vocation))
+        .insert(TilePos::from_world(translation, tile_size))
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                size: Vec2::new(tile_size, tile_size),
+                ..Default::default()
+            },
+            material: materials.add(Color::RED.into()),
+            transform: Transform::from_translation(translation),
+            ..Default::default()
+        })
+        .id()
+}
+
 impl Default for PlayerComponents {
     fn default() -> PlayerComponents {
         PlayerComponents {
@@ -221,9 +525,18 @@ impl Default for PlayerComponents {
                 value: "Unamed".to_string(),
             },
             speed: Speed::default(),
+            vocation: Vocation::default(),
+            skills: Skills::default(),
+            target: LockedTarget::default(),
+            fight_mode: FightMode::default(),
+            hotbar: Hotbar::default(),
+            cooldowns: Cooldowns::default(),
             combat: Combat::default(),
             equipments: Equipments::default(),
+            capacity: Capacity::default(),
             inventory: Vec::new(),
+            explored: ExploredTiles::default(),
+            bestiary: Bestiary::default(),
             body: Body,
             levelling: Levelling::default(),
         }
@@ -0,0 +1,257 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{LockedTarget, Mana},
+    input::PlayerInput,
+    input_map::{Action, Actions},
+    item::Item,
+    map::{TileMap, TilePos},
+    messages::{GameMessage, PlayerMessage},
+    network::is_authoritative,
+    spell::{CastEvent, Spell, SpellTarget},
+    states::AppState,
+    visibility::has_line_of_sight,
+    vocation::Vocation,
+};
+
+/// Number keys 1-0 followed by F1-F12.
+pub const HOTBAR_SLOTS: usize = 22;
+
+const ITEM_COOLDOWN: f32 = 1.;
+const ITEM_RANGE: f32 = 50.;
+
+pub struct HotbarPlugin;
+
+impl Plugin for HotbarPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // Clients send hotbar uses to the server instead, see `network::client_send`.
+        if is_authoritative(app) {
+            app.add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(tick_cooldowns.system())
+                    .with_system(use_hotbar.system().after("actions")),
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum HotbarEntry {
+    Spell(String),
+    Item { title: String, target: SpellTarget },
+}
+
+impl HotbarEntry {
+    /// Spell id or item title, also the key of its cooldown.
+    pub fn id(&self) -> &str {
+        match self {
+            HotbarEntry::Spell(id) => id,
+            HotbarEntry::Item { title, .. } => title,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            HotbarEntry::Spell(id) => Spell::new(id).map_or(id.clone(), |spell| spell.title),
+            HotbarEntry::Item { title, .. } => title.clone(),
+        }
+    }
+}
+
+/// Entries of a player's hotbar slots, bound to `Action::Hotbar` of its input slot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hotbar {
+    pub slots: Vec<Option<HotbarEntry>>,
+}
+
+impl Default for Hotbar {
+    fn default() -> Self {
+        let mut slots = vec![
+            Some(HotbarEntry::Spell("light_healing".to_string())),
+            Some(HotbarEntry::Spell("fire_strike".to_string())),
+            Some(HotbarEntry::Spell("envenom".to_string())),
+            Some(HotbarEntry::Item {
+                title: "Health Potion".to_string(),
+                target: SpellTarget::Caster,
+            }),
+            Some(HotbarEntry::Item {
+                title: "Mana Potion".to_string(),
+                target: SpellTarget::Caster,
+            }),
+        ];
+        slots.resize(HOTBAR_SLOTS, None);
+        Hotbar { slots }
+    }
+}
+
+impl Hotbar {
+    /// Pads or cuts the slots to `HOTBAR_SLOTS`, for hotbars saved with another count.
+    pub fn resized(mut self) -> Self {
+        self.slots.resize(HOTBAR_SLOTS, None);
+        self
+    }
+}
+
+/// Running cooldowns of a player keyed by `HotbarEntry::id`.
+#[derive(Debug, Default)]
+pub struct Cooldowns(pub HashMap<String, Timer>);
+
+impl Cooldowns {
+    pub fn ready(&self, id: &str) -> bool {
+        self.0.get(id).map_or(true, |timer| timer.finished())
+    }
+
+    pub fn percent_left(&self, id: &str) -> f32 {
+        self.0.get(id).map_or(0., |timer| {
+            if timer.finished() {
+                0.
+            } else {
+                timer.percent_left()
+            }
+        })
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
+        for timer in self.0.values_mut() {
+            timer.tick(delta);
+        }
+    }
+
+    fn start(&mut self, id: &str, seconds: f32) {
+        self.0.insert(
+            id.to_string(),
+            Timer::new(Duration::from_secs_f32(seconds), false),
+        );
+    }
+}
+
+fn tick_cooldowns(time: Res<Time>, mut cooldowns: Query<&mut Cooldowns, With<PlayerInput>>) {
+    for mut cooldowns in cooldowns.iter_mut() {
+        cooldowns.tick(time.delta());
+    }
+}
+
+fn use_hotbar(
+    actions: Res<Actions>,
+    mut players: Query<(Entity, &PlayerInput, &Hotbar, &mut Cooldowns, &LockedTarget)>,
+    mut casters: Query<(&Mana, &Vocation, &mut Vec<Item>)>,
+    tiles: Query<&TilePos>,
+    tile_map: Res<TileMap>,
+    mut cast_events: EventWriter<CastEvent>,
+    mut messages: EventWriter<PlayerMessage>,
+) {
+    for (player, input, hotbar, mut cooldowns, target) in players.iter_mut() {
+        for (slot, entry) in hotbar.slots.iter().enumerate() {
+            if !actions.just_pressed_by(input.0, Action::Hotbar(slot as u8)) {
+                continue;
+            }
+            if let Some(entry) = entry {
+                match prepare_cast(
+                    entry,
+                    player,
+                    target.0,
+                    &mut cooldowns,
+                    &mut casters,
+                    &tiles,
+                    &tile_map,
+                ) {
+                    Ok(cast) => cast_events.send(cast),
+                    Err(e) => messages.send(PlayerMessage(player, GameMessage::error(e))),
+                }
+            }
+        }
+    }
+}
+
+/// Checks vocation, cooldown, target, range, line of sight, mana and items for `entry`, then starts its
+/// cooldown and uses up the item.
+pub fn prepare_cast(
+    entry: &HotbarEntry,
+    caster: Entity,
+    target: Option<Entity>,
+    cooldowns: &mut Cooldowns,
+    casters: &mut Query<(&Mana, &Vocation, &mut Vec<Item>)>,
+    tiles: &Query<&TilePos>,
+    tile_map: &TileMap,
+) -> Result<CastEvent, String> {
+    let (title, vocations, cooldown, range, target_kind, mana_cost, effect) = match entry {
+        HotbarEntry::Spell(id) => match Spell::new(id) {
+            Some(spell) => (
+                spell.title,
+                spell.vocations,
+                spell.cooldown,
+                spell.range,
+                spell.target,
+                spell.mana_cost,
+                spell.effect,
+            ),
+            None => return Err(format!("You do not know {}.", id)),
+        },
+        HotbarEntry::Item { title, target } => match Item::new(title) {
+            Some(Item {
+                title,
+                vocations,
+                effect: Some(effect),
+                ..
+            }) => (
+                title,
+                vocations,
+                ITEM_COOLDOWN,
+                ITEM_RANGE,
+                *target,
+                0.,
+                effect,
+            ),
+            _ => return Err(format!("You cannot use {}.", title)),
+        },
+    };
+
+    if !cooldowns.ready(entry.id()) {
+        return Err("You are exhausted.".to_string());
+    }
+    let target_entity = match (target_kind, target) {
+        (SpellTarget::Caster, _) => caster,
+        (SpellTarget::Target, Some(t)) => t,
+        (SpellTarget::Target, None) => return Err("You need a target.".to_string()),
+    };
+    if target_entity != caster {
+        match (tiles.get(caster), tiles.get(target_entity)) {
+            (Ok(a), Ok(b)) if a.world_distance(*b, tile_map.tile_size) <= range => {
+                if !has_line_of_sight(tile_map, *a, *b) {
+                    return Err("You cannot see your target.".to_string());
+                }
+            }
+            _ => return Err("Target is too far away.".to_string()),
+        }
+    }
+
+    let (mana, vocation, mut inventory) = match casters.get_mut(caster) {
+        Ok(caster) => caster,
+        Err(_) => return Err("You cannot do that now.".to_string()),
+    };
+    if !vocation.is_allowed(&vocations) {
+        return Err(format!("{}s cannot use {}.", vocation.title(), title));
+    }
+    if mana.value < mana_cost {
+        return Err("You do not have enough mana.".to_string());
+    }
+    if let HotbarEntry::Item { title, .. } = entry {
+        match inventory.iter().position(|item| &item.title == title) {
+            Some(i) => {
+                inventory.remove(i);
+            }
+            None => return Err(format!("You have no {} left.", title)),
+        }
+    }
+
+    cooldowns.start(entry.id(), cooldown);
+    Ok(CastEvent {
+        caster,
+        target: target_entity,
+        mana_cost,
+        effect,
+    })
+}
@@ -0,0 +1,353 @@
+use bevy::prelude::*;
+
+use crate::{
+    combat::{FightMode, Health, LockedTarget, StatusEffects},
+    entities::{Level, Name},
+    hotbar::{Cooldowns, Hotbar, HOTBAR_SLOTS},
+    input::PlayerInput,
+    input_map::{Action, InputMap},
+    LocalPlayer,
+};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_startup_system(setup_hotbar.system())
+            .add_startup_system(setup_target_frame.system())
+            .add_system(fight_mode_label.system())
+            .add_system(hotbar_slots.system())
+            .add_system(target_frame.system());
+    }
+}
+
+pub struct FightModeLabel;
+
+pub struct HotbarSlotLabel(usize);
+
+/// Dark overlay covering the part of a slot still on cooldown.
+pub struct HotbarCooldownOverlay(usize);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.),
+                    left: Val::Px(5.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/font.ttf"),
+                    font_size: 14.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(FightModeLabel);
+}
+
+fn fight_mode_label(
+    player: Res<LocalPlayer>,
+    fight_modes: Query<&FightMode>,
+    mut labels: Query<&mut Text, With<FightModeLabel>>,
+) {
+    let fight_mode = match player.0.map(|player| fight_modes.get(player)) {
+        Some(Ok(fight_mode)) => fight_mode,
+        _ => return,
+    };
+    for mut text in labels.iter_mut() {
+        text.sections[0].value = format!("Fight mode: {}", fight_mode.label());
+    }
+}
+
+const HOTBAR_SLOT_SIZE: f32 = 30.;
+
+fn setup_hotbar(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font = asset_server.load("fonts/font.ttf");
+    let slot_material = materials.add(Color::rgba(0.1, 0.1, 0.1, 0.8).into());
+    let overlay_material = materials.add(Color::rgba(0., 0., 0., 0.6).into());
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.),
+                    left: Val::Px(5.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::Row,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for slot in 0..HOTBAR_SLOTS {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(HOTBAR_SLOT_SIZE), Val::Px(HOTBAR_SLOT_SIZE)),
+                            margin: Rect::all(Val::Px(2.)),
+                            ..Default::default()
+                        },
+                        material: slot_material.clone(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    "",
+                                    TextStyle {
+                                        font: font.clone(),
+                                        font_size: 9.0,
+                                        color: Color::WHITE,
+                                    },
+                                    Default::default(),
+                                ),
+                                ..Default::default()
+                            })
+                            .insert(HotbarSlotLabel(slot));
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    position_type: PositionType::Absolute,
+                                    position: Rect {
+                                        bottom: Val::Px(0.),
+                                        left: Val::Px(0.),
+                                        ..Default::default()
+                                    },
+                                    size: Size::new(Val::Percent(100.), Val::Percent(0.)),
+                                    ..Default::default()
+                                },
+                                material: overlay_material.clone(),
+                                ..Default::default()
+                            })
+                            .insert(HotbarCooldownOverlay(slot));
+                    });
+            }
+        });
+}
+
+fn hotbar_slots(
+    player: Res<LocalPlayer>,
+    players: Query<(&PlayerInput, &Hotbar, &Cooldowns)>,
+    input_map: Res<InputMap>,
+    mut labels: Query<(&mut Text, &HotbarSlotLabel)>,
+    mut overlays: Query<(&mut Style, &HotbarCooldownOverlay)>,
+) {
+    let (input, hotbar, cooldowns) = match player.0.map(|player| players.get(player)) {
+        Some(Ok(player)) => player,
+        _ => return,
+    };
+    for (mut text, label) in labels.iter_mut() {
+        let key = input_map.label(input.0, Action::Hotbar(label.0 as u8));
+        let title = hotbar.slots[label.0]
+            .as_ref()
+            .map_or(String::new(), |entry| {
+                entry.title().chars().take(6).collect()
+            });
+        text.sections[0].value = format!("{}\n{}", key, title);
+    }
+    for (mut style, overlay) in overlays.iter_mut() {
+        let percent_left = hotbar.slots[overlay.0]
+            .as_ref()
+            .map_or(0., |entry| cooldowns.percent_left(entry.id()));
+        style.size.height = Val::Percent(percent_left * 100.);
+    }
+}
+
+const TARGET_FRAME_WIDTH: f32 = 160.;
+
+pub struct TargetFrame;
+
+pub struct TargetName;
+
+pub struct TargetHealthFill;
+
+pub struct TargetHealthLabel;
+
+/// Level or difficulty, followed by the active status effects.
+pub struct TargetDetails;
+
+fn setup_target_frame(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/font.ttf"),
+        font_size: 12.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.),
+                    left: Val::Percent(40.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(4.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.1, 0.1, 0.1, 0.8).into()),
+            ..Default::default()
+        })
+        .insert(TargetFrame)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section("", text_style.clone(), Default::default()),
+                    ..Default::default()
+                })
+                .insert(TargetName);
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(TARGET_FRAME_WIDTH), Val::Px(14.)),
+                        margin: Rect::all(Val::Px(2.)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgba(0., 0., 0., 0.8).into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                ..Default::default()
+                            },
+                            material: materials.add(Color::GREEN.into()),
+                            ..Default::default()
+                        })
+                        .insert(TargetHealthFill);
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                position: Rect {
+                                    left: Val::Px(4.),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            text: Text::with_section("", text_style.clone(), Default::default()),
+                            ..Default::default()
+                        })
+                        .insert(TargetHealthLabel);
+                });
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section("", text_style, Default::default()),
+                    ..Default::default()
+                })
+                .insert(TargetDetails);
+        });
+}
+
+/// Rough difficulty of monsters without a `Level`, from their health against the player's.
+fn difficulty(target: &Health, player: Option<&Health>) -> &'static str {
+    let ratio = player.map_or(1., |player| target.max_value / player.max_value);
+    if ratio < 0.5 {
+        "Easy"
+    } else if ratio <= 1.5 {
+        "Normal"
+    } else {
+        "Hard"
+    }
+}
+
+fn target_frame(
+    player: Res<LocalPlayer>,
+    locked: Query<&LockedTarget>,
+    targets: Query<(&Name, &Health, Option<&Level>, Option<&StatusEffects>)>,
+    healths: Query<&Health>,
+    mut frames: Query<&mut Style, With<TargetFrame>>,
+    mut fills: Query<&mut Style, (With<TargetHealthFill>, Without<TargetFrame>)>,
+    mut texts: QuerySet<(
+        Query<&mut Text, With<TargetName>>,
+        Query<&mut Text, With<TargetHealthLabel>>,
+        Query<&mut Text, With<TargetDetails>>,
+    )>,
+) {
+    let target = player
+        .0
+        .and_then(|player| locked.get(player).ok())
+        .and_then(|target| target.0)
+        .and_then(|entity| targets.get(entity).ok())
+        .filter(|(_, health, _, _)| health.value > 0.);
+
+    for mut style in frames.iter_mut() {
+        let display = if target.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    let (name, health, level, effects) = match target {
+        Some(target) => target,
+        None => return,
+    };
+
+    let percent = (health.value / health.max_value).max(0.).min(1.) * 100.;
+    for mut style in fills.iter_mut() {
+        if style.size.width != Val::Percent(percent) {
+            style.size.width = Val::Percent(percent);
+        }
+    }
+
+    let mut details = match level {
+        Some(level) => format!("Level {}", level.0),
+        None => difficulty(health, player.0.and_then(|p| healths.get(p).ok())).to_string(),
+    };
+    for effect in effects.iter().flat_map(|effects| effects.0.iter()) {
+        details.push_str(&format!(
+            "\n{} ({:.0}s)",
+            effect.name,
+            effect.timer.duration().as_secs_f32() - effect.timer.elapsed_secs()
+        ));
+    }
+
+    let values = [
+        name.value.clone(),
+        format!("{:.0} / {:.0}", health.value, health.max_value),
+        details,
+    ];
+    for mut text in texts.q0_mut().iter_mut() {
+        set_text(&mut text, &values[0]);
+    }
+    for mut text in texts.q1_mut().iter_mut() {
+        set_text(&mut text, &values[1]);
+    }
+    for mut text in texts.q2_mut().iter_mut() {
+        set_text(&mut text, &values[2]);
+    }
+}
+
+fn set_text(text: &mut Text, value: &str) {
+    if text.sections[0].value != value {
+        text.sections[0].value = value.to_string();
+    }
+}
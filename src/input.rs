@@ -1,10 +1,21 @@
 use super::ai::Monster;
-use std::{process::Child, time::Duration};
+use std::{
+    collections::{HashSet, VecDeque},
+    path::Path,
+    process::Child,
+    time::Duration,
+};
 
 use crate::{
-    combat::{Attack, Combat, CombatText, LockedTarget},
-    config::*,
-    entities::{Body, Player, Speed},
+    combat::{Attack, Combat, CombatText, FightMode, Health, LockedTarget},
+    entities::{Body, PlayerCamera, Speed},
+    input_map::{
+        update_actions, Action, Actions, InputMap, TextInputFocus, INPUT_MAP_PATH, MOVE_ACTIONS,
+    },
+    item::Equipments,
+    map::{find_path, TileMap, TilePos},
+    states::AppState,
+    visibility::{is_seen, VisibleTiles},
     LocalPlayer,
 };
 use bevy::{
@@ -12,7 +23,7 @@ use bevy::{
     input::{keyboard::KeyboardInput, mouse::MouseButtonInput},
     math::{Vec2, Vec3},
     prelude::*,
-    render::{camera::Camera, pipeline::RenderPipeline, render_graph::base::MainPass},
+    render::{pipeline::RenderPipeline, render_graph::base::MainPass},
     sprite::{QUAD_HANDLE, SPRITE_PIPELINE_HANDLE},
 };
 
@@ -24,26 +35,55 @@ impl Plugin for InputPlugin {
             .add_event::<MouseClickEvent>()
             .init_resource::<InputTimer>()
             .init_resource::<Mouse>()
-            .add_event::<MoveEvent>()
-            .add_event::<ChangePositionEvent>()
+            .insert_resource(InputMap::load(Path::new(INPUT_MAP_PATH)))
+            .init_resource::<Actions>()
+            .init_resource::<TextInputFocus>()
             .add_system(track_mouse_position.system())
             .add_system(track_world_mouse_debug.system())
-            .add_system(input_handler.system())
-            .add_system(mouse_position_trigger.system())
-            .add_system(movement_system.system())
-            .add_system(get_entity_at_mouse_position.system())
-            .add_system(lock_on_target.system())
-            .add_system(update_player_timers.system())
+            .add_system(update_actions.system().label("actions"))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(input_handler.system().after("actions"))
+                    .with_system(mouse_position_trigger.system())
+                    .with_system(follow_click_path.system().after("actions"))
+                    .with_system(toggle_fight_mode.system().after("actions"))
+                    .with_system(fight_movement.system().after("actions"))
+                    .with_system(get_entity_at_mouse_position.system())
+                    .with_system(lock_on_target.system())
+                    .with_system(cycle_target.system().after("actions"))
+                    .with_system(highlight_target.system()),
+            )
             .insert_resource(EntityAtMouse(None));
     }
 }
 
-fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+/// Moves, collisions and the timers pacing moves and attacks, with or without anyone at the
+/// keyboard.
+pub struct MovementPlugin;
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<MoveEvent>()
+            .add_event::<ChangePositionEvent>()
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(movement_system.system())
+                    .with_system(animate_movement.system())
+                    .with_system(update_player_timers.system()),
+            );
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    tile_map: Res<TileMap>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
     commands
         .spawn()
         .insert_bundle(SpriteBundle {
             sprite: Sprite {
-                size: Vec2::new(TILE_SIZE, TILE_SIZE),
+                size: Vec2::new(tile_map.tile_size, tile_map.tile_size),
                 ..Default::default()
             },
             material: materials
@@ -54,130 +94,327 @@ fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
         .insert(MousePositionDebug);
 }
 
-fn coordinate(xy: Vec2) -> Vec2 {
-    let x_rest = xy.x % TILE_SIZE;
-    let y_rest = xy.y % TILE_SIZE;
-    let x_div = xy.x as i32 / TILE_SIZE as i32;
-    let y_div = xy.y as i32 / TILE_SIZE as i32;
-    let mut x_pos = x_div * TILE_SIZE as i32;
+fn coordinate(xy: Vec2, tile_size: f32) -> Vec2 {
+    let x_rest = xy.x % tile_size;
+    let y_rest = xy.y % tile_size;
+    let x_div = xy.x as i32 / tile_size as i32;
+    let y_div = xy.y as i32 / tile_size as i32;
+    let mut x_pos = x_div * tile_size as i32;
     if x_rest > 0. {
-        x_pos += TILE_SIZE as i32;
+        x_pos += tile_size as i32;
     }
-    let mut y_pos = y_div * TILE_SIZE as i32;
+    let mut y_pos = y_div * tile_size as i32;
     if y_rest > 0. {
-        y_pos += TILE_SIZE as i32;
+        y_pos += tile_size as i32;
     }
     let position = Vec2::new(x_pos as f32, y_pos as f32);
     position
 }
 
-#[derive(Debug)]
-pub struct MoveEvent(pub Entity, pub Vec3);
+/// Input slot of the keyboard and mouse. The console and the inventory window, typed and
+/// clicked on the shared screen, act for the player in this slot.
+pub const KEYBOARD_SLOT: u8 = 0;
 
-#[derive(Default, Debug)]
-struct InputTimer(Timer);
+/// Marks a player driven by this machine's input: the `Binding`s of its slot. Other players
+/// are moved by the AI or over the network.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerInput(pub u8);
 
-fn collision_check(spr1: CollisionObject, spr2: CollisionObject) -> bool {
-    if spr1.translation.x < spr2.translation.x + spr2.size.x
-        && spr1.translation.x + spr1.size.x > spr2.translation.x
-        && spr1.translation.y < spr2.translation.y + spr2.size.y
-        && spr1.translation.y + spr1.size.y > spr2.translation.y
-    {
-        return true;
-    }
-    false
+/// The player driven by input `slot`, if one is spawned.
+pub fn slot_player(players: &Query<(Entity, &PlayerInput)>, slot: u8) -> Option<Entity> {
+    players
+        .iter()
+        .find(|(_, input)| input.0 == slot)
+        .map(|(player, _)| player)
 }
 
-struct CollisionObject {
-    translation: Vec3,
-    size: Vec2,
+/// Moves an entity by a tile offset, e.g. `IVec2::new(1, -1)` for one diagonal step.
+#[derive(Debug)]
+pub struct MoveEvent(pub Entity, pub IVec2);
+
+/// Rendered slide of a `Transform` towards the logical `TilePos` it just moved to.
+pub struct MoveAnimation {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub timer: Timer,
 }
 
+#[derive(Default, Debug)]
+struct InputTimer(Timer);
+
 fn get_entity_at_mouse_position(
-    sizes: Query<(Entity, &Transform, &Sprite), (Without<MousePositionDebug>, With<Body>)>,
+    bodies: Query<(Entity, &TilePos), With<Body>>,
     mouse: Res<Mouse>,
+    tile_map: Res<TileMap>,
     mut entity_at_mouse: ResMut<EntityAtMouse>,
 ) {
-    for (entity, transform, sprite) in sizes.iter() {
-        let spr1 = CollisionObject {
-            translation: Vec3::new(
-                mouse.coordinated_position.x,
-                mouse.coordinated_position.y,
-                0.,
-            ),
-            size: Vec2::new(TILE_SIZE, TILE_SIZE),
-        };
-        let spr2 = CollisionObject {
-            translation: transform.translation,
-            size: sprite.size,
-        };
-        if collision_check(spr1, spr2) {
-            entity_at_mouse.0 = Some(entity);
-            break;
-        } else {
-            entity_at_mouse.0 = None;
+    let mouse_tile = TilePos::from_world(mouse.coordinated_position.extend(0.), tile_map.tile_size);
+    entity_at_mouse.0 = bodies
+        .iter()
+        .find(|(_, tile)| **tile == mouse_tile)
+        .map(|(entity, _)| entity);
+}
+
+/// Path queued by left-clicking a tile, walked one `MoveEvent` at a time.
+pub struct ClickPath {
+    pub destination: TilePos,
+    pub steps: VecDeque<TilePos>,
+    pub marker: Entity,
+}
+
+pub struct DestinationMarker;
+
+/// Tiles a mover can't step into: walls and every other body.
+fn blocked_tiles(
+    tile_map: &TileMap,
+    bodies: &Query<(Entity, &TilePos), With<Body>>,
+    mover: Entity,
+) -> HashSet<TilePos> {
+    let mut blocked = tile_map.walls.clone();
+    for (entity, tile) in bodies.iter() {
+        if entity != mover {
+            blocked.insert(*tile);
         }
     }
+    blocked
 }
 
 fn mouse_position_trigger(
+    mut commands: Commands,
     mut events: EventReader<MouseClickEvent>,
     entity_at_mouse: Res<EntityAtMouse>,
+    tile_map: Res<TileMap>,
+    players: Query<(Entity, &PlayerInput)>,
+    bodies: Query<(Entity, &TilePos), With<Body>>,
+    paths: Query<&ClickPath>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for event in events.iter() {
+        if event.action != Action::Select || entity_at_mouse.0.is_some() {
+            continue;
+        }
+        let size = tile_map.tile_size;
+        let destination = TilePos::from_world(event.coordinated_position.extend(0.), size);
+        let player = match slot_player(&players, event.slot) {
+            Some(player) => player,
+            None => continue,
+        };
+        let start = match bodies.get(player) {
+            Ok((_, start)) => *start,
+            Err(_) => continue,
+        };
+        let blocked = blocked_tiles(&tile_map, &bodies, player);
+        if let Some(steps) = find_path(start, destination, &blocked) {
+            if let Ok(path) = paths.get(player) {
+                commands.entity(path.marker).despawn();
+            }
+            let marker = commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        size: Vec2::new(size / 2., size / 2.),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgba(0., 1., 0., 0.4).into()),
+                    transform: Transform::from_translation(destination.to_world(size, 1.)),
+                    ..Default::default()
+                })
+                .insert(DestinationMarker)
+                .id();
+            commands.entity(player).insert(ClickPath {
+                destination,
+                steps: steps.into_iter().collect(),
+                marker,
+            });
+        }
+    }
+}
+
+fn follow_click_path(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    tile_map: Res<TileMap>,
+    mut paths: Query<(Entity, &mut ClickPath, &Speed, Option<&PlayerInput>)>,
+    bodies: Query<(Entity, &TilePos), With<Body>>,
+    mut move_events: EventWriter<MoveEvent>,
+) {
+    for (entity, mut path, speed, input) in paths.iter_mut() {
+        let keyboard_movement = input.map_or(false, |input| {
+            MOVE_ACTIONS.iter().any(|a| actions.pressed_by(input.0, *a))
+        });
+        let current = match bodies.get(entity) {
+            Ok((_, tile)) => *tile,
+            Err(_) => continue,
+        };
+        if path.steps.front() == Some(&current) {
+            path.steps.pop_front();
+        }
+        if keyboard_movement || path.steps.is_empty() {
+            commands.entity(path.marker).despawn();
+            commands.entity(entity).remove::<ClickPath>();
+            continue;
+        }
+        if !speed.interval.finished() {
+            continue;
+        }
+
+        let blocked = blocked_tiles(&tile_map, &bodies, entity);
+        if path.steps.iter().any(|step| blocked.contains(step)) {
+            match find_path(current, path.destination, &blocked) {
+                Some(steps) if !steps.is_empty() => path.steps = steps.into_iter().collect(),
+                _ => {
+                    commands.entity(path.marker).despawn();
+                    commands.entity(entity).remove::<ClickPath>();
+                    continue;
+                }
+            }
+        }
+
+        if let Some(next) = path.steps.front() {
+            move_events.send(MoveEvent(entity, current.delta(*next)));
+        }
+    }
+}
+
+fn toggle_fight_mode(actions: Res<Actions>, mut players: Query<(&PlayerInput, &mut FightMode)>) {
+    for (input, mut fight_mode) in players.iter_mut() {
+        if actions.just_pressed_by(input.0, Action::ToggleFightMode) {
+            *fight_mode = fight_mode.next();
+        }
+    }
+}
+
+/// First step of a path that ends next to `target`.
+fn chase_step(current: TilePos, target: TilePos, blocked: &HashSet<TilePos>) -> Option<TilePos> {
+    let mut blocked = blocked.clone();
+    blocked.remove(&target);
+    find_path(current, target, &blocked)
+        .and_then(|path| path.first().copied())
+        .filter(|step| *step != target)
+}
+
+/// Free neighbour farther away from `target` than `current`, if any.
+fn retreat_step(
+    current: TilePos,
+    target: TilePos,
+    blocked: &HashSet<TilePos>,
+    tile_size: f32,
+) -> Option<TilePos> {
+    let distance = current.world_distance(target, tile_size);
+    current
+        .neighbours()
+        .iter()
+        .filter(|tile| !blocked.contains(tile) && tile.world_distance(target, tile_size) > distance)
+        .max_by(|a, b| {
+            a.world_distance(target, tile_size)
+                .partial_cmp(&b.world_distance(target, tile_size))
+                .unwrap()
+        })
+        .copied()
+}
+
+/// Walks each input player towards (or away from) its `LockedTarget` according to its
+/// `FightMode`. Manual movement and click paths take precedence.
+fn fight_movement(
+    actions: Res<Actions>,
+    tile_map: Res<TileMap>,
+    players: Query<
+        (
+            Entity,
+            &PlayerInput,
+            &FightMode,
+            &Speed,
+            &Attack,
+            Option<&Equipments>,
+            &LockedTarget,
+        ),
+        Without<ClickPath>,
+    >,
+    bodies: Query<(Entity, &TilePos), With<Body>>,
+    mut move_events: EventWriter<MoveEvent>,
 ) {
-    for _ in events.iter() {
-        if let Some(t) = entity_at_mouse.0 {
-            // println!("{:?}", t);
+    for (player, input, fight_mode, speed, attack, equipments, target) in players.iter() {
+        if *fight_mode == FightMode::Stand
+            || MOVE_ACTIONS.iter().any(|a| actions.pressed_by(input.0, *a))
+            || !speed.interval.finished()
+        {
+            continue;
+        }
+        let (current, target_tile) = match (target.0.map(|t| bodies.get(t)), bodies.get(player)) {
+            (Some(Ok((_, target_tile))), Ok((_, current))) => (*current, *target_tile),
+            _ => continue,
+        };
+
+        let size = tile_map.tile_size;
+        let distance = current.world_distance(target_tile, size);
+        let range = attack.reach(equipments.and_then(|e| e.ranged_weapon()), size);
+        let blocked = blocked_tiles(&tile_map, &bodies, player);
+        let step = if distance >= range {
+            chase_step(current, target_tile, &blocked)
+        } else if *fight_mode == FightMode::KeepDistance && distance < range - size {
+            retreat_step(current, target_tile, &blocked, size)
+        } else {
+            None
+        };
+        if let Some(step) = step {
+            move_events.send(MoveEvent(player, current.delta(step)));
         }
     }
 }
 
 fn input_handler(
-    mouse_inputs: Res<Input<MouseButton>>,
-    keyboard_inputs: Res<Input<KeyCode>>,
+    actions: Res<Actions>,
     mouse: Res<Mouse>,
     mut mouse_event: EventWriter<MouseClickEvent>,
     mut move_event: EventWriter<MoveEvent>,
     mut timer: ResMut<InputTimer>,
     time: Res<Time>,
-    player: Res<LocalPlayer>,
+    players: Query<(Entity, &PlayerInput)>,
+    ui_nodes: Query<&Interaction>,
 ) {
     timer.0.tick(time.delta());
     if timer.0.finished() {
-        if mouse_inputs.just_pressed(MouseButton::Left) {
-            mouse_event.send(MouseClickEvent {
-                button: MouseButton::Left,
-                ui_position: mouse.ui_position,
-                world_position: mouse.world_position,
-                coordinated_position: mouse.coordinated_position,
-            })
-        }
-        if mouse_inputs.just_pressed(MouseButton::Right) {
-            mouse_event.send(MouseClickEvent {
-                button: MouseButton::Right,
-                ui_position: mouse.ui_position,
-                world_position: mouse.world_position,
-                coordinated_position: mouse.coordinated_position,
-            })
-        }
+        // Clicks on a UI window are not meant for the world behind it.
+        let over_ui = ui_nodes
+            .iter()
+            .any(|interaction| *interaction != Interaction::None);
+        for (player, input) in players.iter() {
+            let slot = input.0;
+            for action in [Action::Select, Action::AttackTarget].iter() {
+                if actions.just_pressed_by(slot, *action) && !over_ui {
+                    mouse_event.send(MouseClickEvent {
+                        slot,
+                        action: *action,
+                        ui_position: mouse.ui_position,
+                        world_position: mouse.world_position,
+                        coordinated_position: mouse.coordinated_position,
+                    })
+                }
+            }
+
+            // # MOVEMENT # //
 
-        // # MOVEMENT # //
-
-        if keyboard_inputs.pressed(KeyCode::W) {
-            move_event.send(MoveEvent(player.0, Vec3::new(0., TILE_SIZE, 0.)));
-        } else if keyboard_inputs.pressed(KeyCode::A) {
-            move_event.send(MoveEvent(player.0, Vec3::new(-TILE_SIZE, 0., 0.)));
-        } else if keyboard_inputs.pressed(KeyCode::S) {
-            move_event.send(MoveEvent(player.0, Vec3::new(0., -TILE_SIZE, 0.)));
-        } else if keyboard_inputs.pressed(KeyCode::D) {
-            move_event.send(MoveEvent(player.0, Vec3::new(TILE_SIZE, 0., 0.)));
-        } else if keyboard_inputs.pressed(KeyCode::Q) {
-            move_event.send(MoveEvent(player.0, Vec3::new(-TILE_SIZE, TILE_SIZE, 0.)));
-        } else if keyboard_inputs.pressed(KeyCode::E) {
-            move_event.send(MoveEvent(player.0, Vec3::new(TILE_SIZE, TILE_SIZE, 0.)));
-        } else if keyboard_inputs.pressed(KeyCode::Z) {
-            move_event.send(MoveEvent(player.0, Vec3::new(-TILE_SIZE, -TILE_SIZE, 0.)));
-        } else if keyboard_inputs.pressed(KeyCode::C) {
-            move_event.send(MoveEvent(player.0, Vec3::new(TILE_SIZE, -TILE_SIZE, 0.)));
+            let direction = if actions.pressed_by(slot, Action::MoveUp) {
+                Some(IVec2::new(0, 1))
+            } else if actions.pressed_by(slot, Action::MoveLeft) {
+                Some(IVec2::new(-1, 0))
+            } else if actions.pressed_by(slot, Action::MoveDown) {
+                Some(IVec2::new(0, -1))
+            } else if actions.pressed_by(slot, Action::MoveRight) {
+                Some(IVec2::new(1, 0))
+            } else if actions.pressed_by(slot, Action::MoveUpLeft) {
+                Some(IVec2::new(-1, 1))
+            } else if actions.pressed_by(slot, Action::MoveUpRight) {
+                Some(IVec2::new(1, 1))
+            } else if actions.pressed_by(slot, Action::MoveDownLeft) {
+                Some(IVec2::new(-1, -1))
+            } else if actions.pressed_by(slot, Action::MoveDownRight) {
+                Some(IVec2::new(1, -1))
+            } else {
+                None
+            };
+            if let Some(direction) = direction {
+                move_event.send(MoveEvent(player, direction));
+            }
         }
         timer.0.reset()
     }
@@ -198,11 +435,19 @@ fn track_world_mouse_debug(
 fn track_mouse_position(
     mut moved: EventReader<CursorMoved>,
     mut mouse: ResMut<Mouse>,
-    mut player_camera: Query<(&Camera, &GlobalTransform)>,
+    player: Res<LocalPlayer>,
+    tile_map: Res<TileMap>,
+    player_cameras: Query<(&PlayerCamera, &GlobalTransform)>,
     windows: ResMut<Windows>,
 ) {
     let window = windows.get_primary().unwrap();
-    let camera = player_camera.single_mut().unwrap().1;
+    let camera = match player_cameras
+        .iter()
+        .find(|(owner, _)| player.0 == Some(owner.0))
+    {
+        Some((_, camera)) => camera,
+        None => return,
+    };
     let camera_offset_x = camera.translation.x;
     let camera_offset_y = camera.translation.y;
     let event = moved.iter().last();
@@ -215,49 +460,71 @@ fn track_mouse_position(
         camera_offset_x + mouse.position.x - (window.width() / 2.),
         camera_offset_y + mouse.position.y - (window.height() / 2.),
     );
-    mouse.coordinated_position =
-        coordinate(mouse.world_position - Vec2::new(TILE_SIZE / 2., TILE_SIZE / 2.));
+    let half_tile = tile_map.tile_size / 2.;
+    mouse.coordinated_position = coordinate(
+        mouse.world_position - Vec2::new(half_tile, half_tile),
+        tile_map.tile_size,
+    );
 }
 
 #[derive(Debug)]
 struct ChangePositionEvent(Entity, Vec3);
 
 fn movement_system(
+    mut commands: Commands,
     mut move_events: EventReader<MoveEvent>,
+    tile_map: Res<TileMap>,
     mut queryset: QuerySet<(
-        Query<(&mut Speed, &mut Transform), With<Body>>,
-        Query<(Entity, &Transform), With<Body>>,
+        Query<(&mut Speed, &mut TilePos, &Transform), With<Body>>,
+        Query<&TilePos, With<Body>>,
     )>,
 ) {
     for event in move_events.iter() {
-        let mut r#move = false;
-        let mut collision = false;
-        let result = queryset.q0_mut().get_mut(event.0);
-        if let Ok((speed, transform)) = result {
+        let mut destination = None;
+        if let Ok((speed, tile, _)) = queryset.q0_mut().get_mut(event.0) {
             if speed.interval.finished() {
-                let delta = transform.translation.clone() + event.1;
-                for (_, transform2) in queryset.q1().iter() {
-                    if transform2.translation == delta {
-                        collision = true;
-                    }
-                }
-                if !collision {
-                    r#move = true;
-                }
+                destination = Some(tile.offset(event.1));
             }
         }
-        if r#move {
-            if let Ok((mut speed, mut transform)) = queryset.q0_mut().get_mut(event.0) {
-                let duration =
-                    Duration::from_millis(speed.base_interval as u64 - speed.value as u64);
+        if let Some(destination) = destination {
+            let collision = tile_map.is_wall(destination)
+                || queryset.q1().iter().any(|tile| *tile == destination);
+            if collision {
+                continue;
+            }
+            if let Ok((mut speed, mut tile, transform)) = queryset.q0_mut().get_mut(event.0) {
+                let mut step = speed.base_interval - speed.value;
+                if event.1.x != 0 && event.1.y != 0 {
+                    step *= std::f32::consts::SQRT_2;
+                }
+                let duration = Duration::from_millis(step as u64);
                 speed.interval.set_duration(duration);
-                transform.translation += event.1;
-                speed.interval.reset()
+                speed.interval.reset();
+                *tile = destination;
+                commands.entity(event.0).insert(MoveAnimation {
+                    from: transform.translation,
+                    to: destination.to_world(tile_map.tile_size, transform.translation.z),
+                    timer: Timer::new(duration, false),
+                });
             }
         }
     }
 }
 
+fn animate_movement(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut MoveAnimation)>,
+) {
+    for (entity, mut transform, mut animation) in query.iter_mut() {
+        animation.timer.tick(time.delta());
+        transform.translation = animation.from.lerp(animation.to, animation.timer.percent());
+        if animation.timer.finished() {
+            commands.entity(entity).remove::<MoveAnimation>();
+        }
+    }
+}
+
 fn update_player_timers(
     time: Res<Time>,
     mut speeds: Query<&mut Speed>,
@@ -285,7 +552,9 @@ pub struct Mouse {
 
 #[derive(Debug)]
 pub struct MouseClickEvent {
-    pub button: MouseButton,
+    /// Input slot of the binding that clicked.
+    pub slot: u8,
+    pub action: Action,
     pub ui_position: Vec2,
     pub world_position: Vec2,
     pub coordinated_position: Vec2,
@@ -295,52 +564,116 @@ pub struct MouseClickEvent {
 pub struct EntityAtMouse(pub Option<Entity>);
 
 // RightClick
+/// Clicks lock on the entity under the mouse, unless it is out of sight.
 pub fn lock_on_target(
-    mut commands: Commands,
     entity: Res<EntityAtMouse>,
-    mut target: ResMut<LockedTarget>,
+    player: Res<LocalPlayer>,
+    visible: Res<VisibleTiles>,
+    tiles: Query<&TilePos>,
     mut mouse_events: EventReader<MouseClickEvent>,
-    localplayer: Res<LocalPlayer>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    children_query: Query<(Entity, &Parent), With<LockedSprite>>,
+    mut players: Query<(Entity, &PlayerInput, &mut LockedTarget)>,
 ) {
     for event in mouse_events.iter() {
-        if event.button == MouseButton::Right {
-            match (entity.0, target.0) {
-                (None, None) => (),
-                (None, Some(_)) => (),
-                (Some(e), None) => {
-                    if e == localplayer.0 {
-                    } else {
-                        if let Some((child, _)) = children_query.iter().last() {
-                            commands.entity(child).despawn();
-                        }
-                        target.0 = Some(e);
-                        push_hover_children(e, &mut commands, &mut materials)
-                    }
-                }
-                (Some(e), Some(t)) => {
-                    if e == t {
-                        target.0 = None;
-                        if let Some((child, _)) = children_query.iter().last() {
-                            commands.entity(child).despawn();
-                        }
-                    } else if e == localplayer.0 {
-                    } else {
-                        if let Some((child, _)) = children_query.iter().last() {
-                            commands.entity(child).despawn();
-                        }
-                        target.0 = Some(e);
-                        push_hover_children(e, &mut commands, &mut materials)
-                    }
+        let hidden = entity
+            .0
+            .map_or(false, |e| !is_seen(&player, &visible, tiles.get(e).ok()));
+        if event.action == Action::AttackTarget && !hidden {
+            let clicker = players
+                .iter_mut()
+                .find(|(_, input, _)| input.0 == event.slot);
+            if let Some((player, _, mut target)) = clicker {
+                match (entity.0, target.0) {
+                    (None, None) => (),
+                    (None, Some(_)) => (),
+                    (Some(e), _) if e == player => (),
+                    (Some(e), Some(t)) if e == t => target.0 = None,
+                    (Some(e), _) => target.0 = Some(e),
                 }
             }
         }
     }
-    if let Some(t) = target.0 {
-        if let Ok(_) = children_query.get(t) {
-            push_hover_children(t, &mut commands, &mut materials)
+}
+
+/// Tab / Shift+Tab through living monsters in sight, nearest first.
+fn cycle_target(
+    actions: Res<Actions>,
+    windows: Res<Windows>,
+    player: Res<LocalPlayer>,
+    visible: Res<VisibleTiles>,
+    tile_map: Res<TileMap>,
+    mut players: Query<(&PlayerInput, &TilePos, &mut LockedTarget)>,
+    monsters: Query<(Entity, &TilePos, &Health), With<Monster>>,
+) {
+    let view = match windows.get_primary() {
+        Some(window) => Vec2::new(window.width() / 2., window.height() / 2.),
+        None => return,
+    };
+    let size = tile_map.tile_size;
+    for (input, player_tile, mut target) in players.iter_mut() {
+        if actions.just_pressed_by(input.0, Action::ClearTarget) {
+            target.0 = None;
+            continue;
+        }
+        let forward = actions.just_pressed_by(input.0, Action::NextTarget);
+        let backward = actions.just_pressed_by(input.0, Action::PreviousTarget);
+        if !forward && !backward {
+            continue;
+        }
+
+        let player_position = player_tile.to_world(size, 0.);
+        let mut candidates: Vec<(Entity, f32)> = monsters
+            .iter()
+            .filter(|(_, tile, health)| {
+                let offset = (tile.to_world(size, 0.) - player_position).abs();
+                health.value > 0.
+                    && offset.x <= view.x
+                    && offset.y <= view.y
+                    && is_seen(&player, &visible, Some(tile))
+            })
+            .map(|(entity, tile, _)| (entity, player_tile.world_distance(*tile, size)))
+            .collect();
+        if candidates.is_empty() {
+            continue;
         }
+        candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap().then(a.0.id().cmp(&b.0.id())));
+
+        let current = target
+            .0
+            .and_then(|t| candidates.iter().position(|(e, _)| *e == t));
+        let len = candidates.len();
+        let next = match (current, forward) {
+            (Some(i), true) => (i + 1) % len,
+            (Some(i), false) => (i + len - 1) % len,
+            (None, true) => 0,
+            (None, false) => len - 1,
+        };
+        target.0 = Some(candidates[next].0);
+    }
+}
+
+/// Keeps the red highlight on the target of the `LocalPlayer`.
+fn highlight_target(
+    mut commands: Commands,
+    player: Res<LocalPlayer>,
+    targets: Query<&LockedTarget>,
+    highlights: Query<(Entity, &Parent), With<LockedSprite>>,
+    tile_map: Res<TileMap>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let target = player
+        .0
+        .and_then(|player| targets.get(player).ok())
+        .and_then(|target| target.0);
+    let mut highlighted = false;
+    for (highlight, parent) in highlights.iter() {
+        if target == Some(parent.0) && !highlighted {
+            highlighted = true;
+        } else {
+            commands.entity(highlight).despawn();
+        }
+    }
+    if let (Some(target), false) = (target, highlighted) {
+        push_hover_children(target, &mut commands, &mut materials, tile_map.tile_size);
     }
 }
 
@@ -350,9 +683,14 @@ fn push_hover_children(
     entity: Entity,
     commands: &mut Commands,
     materials: &mut ResMut<Assets<ColorMaterial>>,
+    tile_size: f32,
 ) {
     commands.entity(entity).with_children(|entity| {
         entity.spawn_bundle(HoverBundle {
+            sprite: Sprite {
+                size: Vec2::new(tile_size, tile_size),
+                ..Default::default()
+            },
             material: materials.add(Color::rgba(1., 0., 0., 0.2).into()),
             transform: Transform::from_xyz(0., 0., 0.),
             ..Default::default()
@@ -388,10 +726,7 @@ impl Default for HoverBundle {
             },
             main_pass: MainPass,
             draw: Default::default(),
-            sprite: Sprite {
-                size: Vec2::new(TILE_SIZE, TILE_SIZE),
-                ..Default::default()
-            },
+            sprite: Default::default(),
             material: Default::default(),
             transform: Transform::from_xyz(0., 0., 10.),
             global_transform: Default::default(),
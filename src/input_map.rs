@@ -0,0 +1,336 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{read_ron, write_ron};
+
+pub const INPUT_MAP_PATH: &str = "config/input.ron";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+    Select,
+    AttackTarget,
+    NextTarget,
+    PreviousTarget,
+    ClearTarget,
+    ToggleFightMode,
+    Hotbar(u8),
+    OpenInventory,
+    OpenCharacter,
+    ToggleMap,
+    MapZoomIn,
+    MapZoomOut,
+    MapPanUp,
+    MapPanDown,
+    MapPanLeft,
+    MapPanRight,
+    /// Ctrl+S and Ctrl+L by default. They used to be F5 and F9, which are now hotbar slots.
+    QuickSave,
+    QuickLoad,
+    ToggleConsole,
+    Pause,
+}
+
+pub const MOVE_ACTIONS: [Action; 8] = [
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::MoveUpLeft,
+    Action::MoveUpRight,
+    Action::MoveDownLeft,
+    Action::MoveDownRight,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Modifier {
+    Shift,
+    Control,
+    Alt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputButton {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    pub button: InputButton,
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+    /// Input slot of the player this binding drives, see `input::PlayerInput`.
+    #[serde(default)]
+    pub slot: u8,
+}
+
+impl Binding {
+    pub fn key(key: KeyCode) -> Self {
+        Binding {
+            button: InputButton::Key(key),
+            modifiers: vec![],
+            slot: 0,
+        }
+    }
+
+    pub fn mouse(button: MouseButton) -> Self {
+        Binding {
+            button: InputButton::Mouse(button),
+            modifiers: vec![],
+            slot: 0,
+        }
+    }
+
+    pub fn with(mut self, modifier: Modifier) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+
+    /// Drives the player in `slot` instead of the keyboard and mouse player.
+    pub fn for_slot(mut self, slot: u8) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    /// A binding is more specific than another on the same button when it needs more modifiers,
+    /// e.g. `Shift+Tab` wins over `Tab` while shift is held.
+    fn overrides(&self, other: &Binding) -> bool {
+        self.button == other.button
+            && self.modifiers.len() > other.modifiers.len()
+            && other.modifiers.iter().all(|m| self.modifiers.contains(m))
+    }
+}
+
+/// Maps abstract actions to one or more key/mouse bindings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputMap {
+    pub bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut bindings = HashMap::new();
+        bindings.insert(Action::MoveUp, vec![Binding::key(KeyCode::W)]);
+        bindings.insert(Action::MoveLeft, vec![Binding::key(KeyCode::A)]);
+        bindings.insert(Action::MoveDown, vec![Binding::key(KeyCode::S)]);
+        bindings.insert(Action::MoveRight, vec![Binding::key(KeyCode::D)]);
+        bindings.insert(Action::MoveUpLeft, vec![Binding::key(KeyCode::Q)]);
+        bindings.insert(Action::MoveUpRight, vec![Binding::key(KeyCode::E)]);
+        bindings.insert(Action::MoveDownLeft, vec![Binding::key(KeyCode::Z)]);
+        bindings.insert(Action::MoveDownRight, vec![Binding::key(KeyCode::C)]);
+        bindings.insert(Action::Select, vec![Binding::mouse(MouseButton::Left)]);
+        bindings.insert(
+            Action::AttackTarget,
+            vec![Binding::mouse(MouseButton::Right)],
+        );
+        bindings.insert(Action::NextTarget, vec![Binding::key(KeyCode::Tab)]);
+        bindings.insert(
+            Action::PreviousTarget,
+            vec![Binding::key(KeyCode::Tab).with(Modifier::Shift)],
+        );
+        bindings.insert(Action::ClearTarget, vec![Binding::key(KeyCode::Escape)]);
+        bindings.insert(Action::ToggleFightMode, vec![Binding::key(KeyCode::F)]);
+        bindings.insert(Action::OpenInventory, vec![Binding::key(KeyCode::I)]);
+        bindings.insert(Action::OpenCharacter, vec![Binding::key(KeyCode::P)]);
+        bindings.insert(Action::ToggleMap, vec![Binding::key(KeyCode::M)]);
+        bindings.insert(Action::MapZoomIn, vec![Binding::key(KeyCode::Equals)]);
+        bindings.insert(Action::MapZoomOut, vec![Binding::key(KeyCode::Minus)]);
+        bindings.insert(Action::MapPanUp, vec![Binding::key(KeyCode::Up)]);
+        bindings.insert(Action::MapPanDown, vec![Binding::key(KeyCode::Down)]);
+        bindings.insert(Action::MapPanLeft, vec![Binding::key(KeyCode::Left)]);
+        bindings.insert(Action::MapPanRight, vec![Binding::key(KeyCode::Right)]);
+        bindings.insert(
+            Action::QuickSave,
+            vec![Binding::key(KeyCode::S).with(Modifier::Control)],
+        );
+        bindings.insert(
+            Action::QuickLoad,
+            vec![Binding::key(KeyCode::L).with(Modifier::Control)],
+        );
+        bindings.insert(Action::ToggleConsole, vec![Binding::key(KeyCode::Grave)]);
+        bindings.insert(
+            Action::Pause,
+            vec![
+                Binding::key(KeyCode::Pause),
+                Binding::key(KeyCode::Escape).with(Modifier::Shift),
+            ],
+        );
+        let slots = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+            KeyCode::Key0,
+            KeyCode::F1,
+            KeyCode::F2,
+            KeyCode::F3,
+            KeyCode::F4,
+            KeyCode::F5,
+            KeyCode::F6,
+            KeyCode::F7,
+            KeyCode::F8,
+            KeyCode::F9,
+            KeyCode::F10,
+            KeyCode::F11,
+            KeyCode::F12,
+        ];
+        for (slot, key) in slots.iter().enumerate() {
+            bindings.insert(Action::Hotbar(slot as u8), vec![Binding::key(*key)]);
+        }
+        InputMap { bindings }
+    }
+}
+
+impl InputMap {
+    /// Short label of the first binding of `action` for `slot`, e.g. `1` or `F5`.
+    pub fn label(&self, slot: u8, action: Action) -> String {
+        let binding = match self
+            .bindings
+            .get(&action)
+            .and_then(|b| b.iter().find(|binding| binding.slot == slot))
+        {
+            Some(binding) => binding,
+            None => return String::new(),
+        };
+        let button = match binding.button {
+            InputButton::Key(key) => format!("{:?}", key).replace("Key", ""),
+            InputButton::Mouse(button) => format!("{:?}", button),
+        };
+        binding
+            .modifiers
+            .iter()
+            .map(|m| format!("{:?}+", m))
+            .chain(std::iter::once(button))
+            .collect()
+    }
+
+    /// Loads the user bindings, falling back to the defaults for missing actions.
+    pub fn load(path: &Path) -> Self {
+        let mut input_map = InputMap::default();
+        match read_ron::<InputMap>(path) {
+            Ok(user) => input_map.bindings.extend(user.bindings),
+            Err(e) => {
+                println!("Using default key bindings ({:?}): {:?}", path, e);
+                if !path.exists() {
+                    if let Err(e) = write_ron(path, &input_map) {
+                        println!("Failed to write default key bindings: {:?}", e);
+                    }
+                }
+            }
+        }
+        input_map
+    }
+}
+
+/// Actions triggered this frame, filled from the `InputMap`, along with the input slot of the
+/// binding that triggered them.
+#[derive(Debug, Default)]
+pub struct Actions {
+    pressed: HashSet<(u8, Action)>,
+    just_pressed: HashSet<(u8, Action)>,
+}
+
+impl Actions {
+    /// Whether any slot holds `action`, for actions on the shared screen such as menus.
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.iter().any(|(_, a)| *a == action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.iter().any(|(_, a)| *a == action)
+    }
+
+    /// Whether the player in `slot` holds `action`.
+    pub fn pressed_by(&self, slot: u8, action: Action) -> bool {
+        self.pressed.contains(&(slot, action))
+    }
+
+    pub fn just_pressed_by(&self, slot: u8, action: Action) -> bool {
+        self.just_pressed.contains(&(slot, action))
+    }
+}
+
+/// Set while a text field such as the console has the keyboard, only `ToggleConsole` fires then.
+#[derive(Debug, Default)]
+pub struct TextInputFocus(pub bool);
+
+fn modifier_held(modifier: Modifier, keyboard_inputs: &Input<KeyCode>) -> bool {
+    match modifier {
+        Modifier::Shift => {
+            keyboard_inputs.pressed(KeyCode::LShift) || keyboard_inputs.pressed(KeyCode::RShift)
+        }
+        Modifier::Control => {
+            keyboard_inputs.pressed(KeyCode::LControl) || keyboard_inputs.pressed(KeyCode::RControl)
+        }
+        Modifier::Alt => {
+            keyboard_inputs.pressed(KeyCode::LAlt) || keyboard_inputs.pressed(KeyCode::RAlt)
+        }
+    }
+}
+
+pub fn update_actions(
+    input_map: Res<InputMap>,
+    keyboard_inputs: Res<Input<KeyCode>>,
+    mouse_inputs: Res<Input<MouseButton>>,
+    focus: Res<TextInputFocus>,
+    mut actions: ResMut<Actions>,
+) {
+    let button_state = |button: InputButton| match button {
+        InputButton::Key(key) => (
+            keyboard_inputs.pressed(key),
+            keyboard_inputs.just_pressed(key),
+        ),
+        InputButton::Mouse(button) => (
+            mouse_inputs.pressed(button),
+            mouse_inputs.just_pressed(button),
+        ),
+    };
+
+    let mut matches = vec![];
+    for (action, bindings) in input_map.bindings.iter() {
+        if focus.0 && *action != Action::ToggleConsole {
+            continue;
+        }
+        for binding in bindings.iter() {
+            let (pressed, just_pressed) = button_state(binding.button);
+            if pressed
+                && binding
+                    .modifiers
+                    .iter()
+                    .all(|m| modifier_held(*m, &keyboard_inputs))
+            {
+                matches.push((*action, binding, just_pressed));
+            }
+        }
+    }
+
+    actions.pressed.clear();
+    actions.just_pressed.clear();
+    for (action, binding, just_pressed) in matches.iter() {
+        if matches.iter().any(|(_, other, _)| other.overrides(binding)) {
+            continue;
+        }
+        actions.pressed.insert((binding.slot, *action));
+        if *just_pressed {
+            actions.just_pressed.insert((binding.slot, *action));
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use bevy::prelude::*;
+
+use crate::{
+    input::{PlayerInput, KEYBOARD_SLOT},
+    input_map::{Action, Actions},
+    item::{Capacity, Equipments, Item, ItemSlot},
+    messages::GameMessage,
+    states::AppState,
+    vocation::Vocation,
+    LocalPlayer,
+};
+
+/// Inventory slots shown in the grid, five per row.
+pub const INVENTORY_SLOTS: usize = 20;
+const INVENTORY_COLUMNS: usize = 5;
+const SLOT_SIZE: f32 = 34.;
+
+/// Paper doll layout of the equipment slots, top row first.
+const EQUIPMENT_ROWS: [&[ItemSlot]; 4] = [
+    &[ItemSlot::LeftFinger, ItemSlot::Head, ItemSlot::Neck],
+    &[ItemSlot::MainHand, ItemSlot::Chest, ItemSlot::OffHand],
+    &[ItemSlot::RightFinger, ItemSlot::Legs, ItemSlot::Boots],
+    &[ItemSlot::Ammo],
+];
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<DraggedItem>()
+            .add_startup_system(setup.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(toggle_inventory.system().after("actions"))
+                    .with_system(drag_items.system()),
+            )
+            .add_system(inventory_slots.system())
+            .add_system(item_tooltip.system());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlotRef {
+    Equipment(ItemSlot),
+    Inventory(usize),
+}
+
+impl SlotRef {
+    fn item<'a>(self, equipments: &'a Equipments, inventory: &'a [Item]) -> Option<&'a Item> {
+        match self {
+            SlotRef::Equipment(slot) => equipments.get(slot),
+            SlotRef::Inventory(i) => inventory.get(i),
+        }
+    }
+}
+
+pub struct InventoryWindow;
+
+pub struct InventorySlot(pub SlotRef);
+
+pub struct InventorySlotLabel(pub SlotRef);
+
+pub struct ItemTooltip;
+
+/// Slot the left mouse button was pressed on, dropped on the hovered slot when released.
+#[derive(Debug, Default)]
+pub struct DraggedItem(pub Option<SlotRef>);
+
+/// Weight of everything a player wears and carries, limited by its `Capacity`.
+pub fn carried_weight(equipments: &Equipments, inventory: &[Item]) -> f32 {
+    equipments.weight() + inventory.iter().map(Item::total_weight).sum::<f32>()
+}
+
+/// Puts `item` in the inventory unless it would take the player over its `capacity` or every
+/// one of the `INVENTORY_SLOTS` is taken. Items entering an inventory go through here.
+pub fn add_item(
+    item: Item,
+    capacity: Capacity,
+    equipments: &Equipments,
+    inventory: &mut Vec<Item>,
+) -> Result<(), String> {
+    if carried_weight(equipments, inventory) + item.total_weight() > capacity.0 {
+        return Err(format!("You cannot carry {}.", item.title));
+    }
+    if inventory.len() >= INVENTORY_SLOTS {
+        return Err("Your inventory is full.".to_string());
+    }
+    inventory.push(item);
+    Ok(())
+}
+
+/// Moves an item between the equipment and the inventory, swapping with whatever is in `to`.
+/// Only items of the wearer's vocation can be equipped. Worn and carried items both count
+/// towards the `Capacity`, so moves never change the weight.
+pub fn move_item(
+    from: SlotRef,
+    to: SlotRef,
+    vocation: Vocation,
+    equipments: &mut Equipments,
+    inventory: &mut Vec<Item>,
+) -> Result<(), String> {
+    match (from, to) {
+        (SlotRef::Inventory(i), SlotRef::Inventory(j)) => {
+            if i < inventory.len() {
+                let item = inventory.remove(i);
+                inventory.insert(j.min(inventory.len()), item);
+            }
+        }
+        (SlotRef::Inventory(i), SlotRef::Equipment(slot)) => {
+            match inventory.get(i) {
+                Some(item) if !slot.accepts(item) => {
+                    return Err(format!("{} doesn't fit there.", item.title))
+                }
+                Some(item) if !vocation.is_allowed(&item.vocations) => {
+                    return Err(format!("{}s cannot use {}.", vocation.title(), item.title))
+                }
+                Some(_) => (),
+                None => return Ok(()),
+            }
+            let item = inventory.remove(i);
+            if let Some(old) = equipments.slot_mut(slot).and_then(|s| s.replace(item)) {
+                inventory.insert(i, old);
+            }
+        }
+        (SlotRef::Equipment(slot), SlotRef::Inventory(j)) => {
+            let equipped = match equipments.slot_mut(slot) {
+                Some(equipped) => equipped,
+                None => return Ok(()),
+            };
+            let item = match equipped.take() {
+                Some(item) => item,
+                None => return Ok(()),
+            };
+            if j < inventory.len()
+                && slot.accepts(&inventory[j])
+                && vocation.is_allowed(&inventory[j].vocations)
+            {
+                *equipped = Some(inventory.remove(j));
+            } else if inventory.len() >= INVENTORY_SLOTS {
+                *equipped = Some(item);
+                return Err("Your inventory is full.".to_string());
+            }
+            inventory.insert(j.min(inventory.len()), item);
+        }
+        (SlotRef::Equipment(a), SlotRef::Equipment(b)) => {
+            if a == b {
+                return Ok(());
+            }
+            let item = match equipments.get(a) {
+                Some(item) => item,
+                None => return Ok(()),
+            };
+            if !b.accepts(item) || equipments.get(b).map_or(false, |other| !a.accepts(other)) {
+                return Err(format!("{} doesn't fit there.", item.title));
+            }
+            let item = equipments.slot_mut(a).and_then(|s| s.take());
+            let other = equipments
+                .slot_mut(b)
+                .and_then(|s| std::mem::replace(s, item));
+            if let Some(s) = equipments.slot_mut(a) {
+                *s = other;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn slot_bundle(material: &Handle<ColorMaterial>) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(SLOT_SIZE), Val::Px(SLOT_SIZE)),
+            margin: Rect::all(Val::Px(2.)),
+            ..Default::default()
+        },
+        material: material.clone(),
+        ..Default::default()
+    }
+}
+
+fn row_bundle(material: &Handle<ColorMaterial>) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
+            ..Default::default()
+        },
+        material: material.clone(),
+        ..Default::default()
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font = asset_server.load("fonts/font.ttf");
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: 8.0,
+        color: Color::WHITE,
+    };
+    let none = materials.add(Color::NONE.into());
+    let slot_material = materials.add(Color::rgba(0.1, 0.1, 0.1, 0.8).into());
+
+    let spawn_slot = |parent: &mut ChildBuilder, slot: SlotRef| {
+        parent
+            .spawn_bundle(slot_bundle(&slot_material))
+            .insert(Interaction::default())
+            .insert(InventorySlot(slot))
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section("", text_style.clone(), Default::default()),
+                        ..Default::default()
+                    })
+                    .insert(InventorySlotLabel(slot));
+            });
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(140.),
+                    right: Val::Px(5.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                padding: Rect::all(Val::Px(4.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.2, 0.2, 0.2, 0.9).into()),
+            ..Default::default()
+        })
+        // Keeps clicks on the window from reaching the world.
+        .insert(Interaction::default())
+        .insert(InventoryWindow)
+        .with_children(|parent| {
+            for row in EQUIPMENT_ROWS.iter() {
+                parent
+                    .spawn_bundle(row_bundle(&none))
+                    .with_children(|parent| {
+                        for slot in row.iter() {
+                            spawn_slot(parent, SlotRef::Equipment(*slot));
+                        }
+                    });
+            }
+            for row in 0..INVENTORY_SLOTS / INVENTORY_COLUMNS {
+                parent
+                    .spawn_bundle(row_bundle(&none))
+                    .with_children(|parent| {
+                        for column in 0..INVENTORY_COLUMNS {
+                            spawn_slot(
+                                parent,
+                                SlotRef::Inventory(row * INVENTORY_COLUMNS + column),
+                            );
+                        }
+                    });
+            }
+        });
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font,
+                    font_size: 12.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ItemTooltip);
+}
+
+fn toggle_inventory(
+    actions: Res<Actions>,
+    mut dragged: ResMut<DraggedItem>,
+    mut windows: Query<&mut Style, With<InventoryWindow>>,
+) {
+    if !actions.just_pressed(Action::OpenInventory) {
+        return;
+    }
+    for mut style in windows.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            Display::Flex => {
+                dragged.0 = None;
+                Display::None
+            }
+        };
+    }
+}
+
+/// Drags within the inventory of the mouse's player.
+fn drag_items(
+    mouse_inputs: Res<Input<MouseButton>>,
+    mut dragged: ResMut<DraggedItem>,
+    slots: Query<(&Interaction, &InventorySlot)>,
+    mut players: Query<(&PlayerInput, &Vocation, &mut Equipments, &mut Vec<Item>)>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    let (_, vocation, mut equipments, mut inventory) = match players
+        .iter_mut()
+        .find(|(input, ..)| input.0 == KEYBOARD_SLOT)
+    {
+        Some(player) => player,
+        None => return,
+    };
+    if mouse_inputs.just_pressed(MouseButton::Left) {
+        dragged.0 = slots
+            .iter()
+            .find(|(interaction, _)| **interaction == Interaction::Clicked)
+            .map(|(_, slot)| slot.0)
+            .filter(|slot| slot.item(&equipments, &inventory).is_some());
+    }
+    if mouse_inputs.just_released(MouseButton::Left) {
+        let from = match dragged.0.take() {
+            Some(from) => from,
+            None => return,
+        };
+        let to = slots
+            .iter()
+            .find(|(interaction, _)| **interaction == Interaction::Hovered)
+            .map(|(_, slot)| slot.0);
+        if let Some(to) = to {
+            if let Err(e) = move_item(from, to, *vocation, &mut equipments, &mut inventory) {
+                messages.send(GameMessage::error(e));
+            }
+        }
+    }
+}
+
+fn slot_name(slot: ItemSlot) -> &'static str {
+    match slot {
+        ItemSlot::MainHand => "Main hand",
+        ItemSlot::OffHand => "Off hand",
+        ItemSlot::Neck => "Neck",
+        ItemSlot::Head => "Head",
+        ItemSlot::Chest => "Chest",
+        ItemSlot::Legs => "Legs",
+        ItemSlot::Boots => "Boots",
+        ItemSlot::LeftFinger | ItemSlot::RightFinger => "Ring",
+        ItemSlot::Ammo => "Ammo",
+        ItemSlot::Consumable => "",
+    }
+}
+
+fn inventory_slots(
+    player: Res<LocalPlayer>,
+    players: Query<(&Equipments, &Vec<Item>)>,
+    mut labels: Query<(&mut Text, &InventorySlotLabel)>,
+) {
+    let (equipments, inventory) = match player.0.map(|player| players.get(player)) {
+        Some(Ok(player)) => player,
+        _ => return,
+    };
+    for (mut text, label) in labels.iter_mut() {
+        let (value, color) = match (label.0.item(equipments, inventory), label.0) {
+            (Some(item), _) if item.count > 1 => {
+                (format!("{} x{}", item.title, item.count), Color::WHITE)
+            }
+            (Some(item), _) => (item.title.clone(), Color::WHITE),
+            (None, SlotRef::Equipment(slot)) => (slot_name(slot).to_string(), Color::GRAY),
+            (None, SlotRef::Inventory(_)) => (String::new(), Color::WHITE),
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+fn item_tooltip(
+    windows: Res<Windows>,
+    player: Res<LocalPlayer>,
+    dragged: Res<DraggedItem>,
+    players: Query<(&Equipments, &Vec<Item>)>,
+    slots: Query<(&Interaction, &InventorySlot)>,
+    mut tooltips: Query<(&mut Text, &mut Style), With<ItemTooltip>>,
+) {
+    let (equipments, inventory) = match player.0.map(|player| players.get(player)) {
+        Some(Ok(player)) => player,
+        _ => return,
+    };
+    let cursor = windows
+        .get_primary()
+        .and_then(|window| window.cursor_position());
+    let value = match (dragged.0, cursor) {
+        (_, None) => None,
+        (Some(slot), _) => slot
+            .item(equipments, inventory)
+            .map(|item| item.title.clone()),
+        (None, _) => slots
+            .iter()
+            .find(|(interaction, _)| **interaction == Interaction::Hovered)
+            .and_then(|(_, slot)| slot.0.item(equipments, inventory))
+            .map(|item| {
+                let mut lines = vec![item.title.clone(), item.description.clone()];
+                for attribute in item.attributes.iter() {
+                    lines.push(format!(
+                        "{:?}: {}",
+                        attribute.attribute_type, attribute.value
+                    ));
+                }
+                lines.join("\n")
+            }),
+    };
+
+    for (mut text, mut style) in tooltips.iter_mut() {
+        match (&value, cursor) {
+            (Some(value), Some(cursor)) => {
+                style.display = Display::Flex;
+                style.position = Rect {
+                    left: Val::Px(cursor.x + 12.),
+                    bottom: Val::Px(cursor.y + 12.),
+                    ..Default::default()
+                };
+                if text.sections[0].value != *value {
+                    text.sections[0].value = value.clone();
+                }
+            }
+            _ => {
+                if style.display != Display::None {
+                    style.display = Display::None;
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use crate::combat::{Damage, DamageSet, DamageType};
+use crate::skills::Skill;
+use crate::spell::SpellEffect;
+use crate::vocation::Vocation;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Equipments {
     pub mainhand: Option<Item>,
     pub offhand: Option<Item>,
@@ -11,6 +16,8 @@ pub struct Equipments {
     pub boots: Option<Item>,
     pub leftfinger: Option<Item>,
     pub rightfinger: Option<Item>,
+    #[serde(default)]
+    pub ammo: Option<Item>,
 }
 
 impl Equipments {
@@ -99,6 +106,102 @@ impl Equipments {
     }
 }
 
+impl Equipments {
+    /// The equipment slot itself, `None` for slots that can't be worn like `Consumable`.
+    pub fn slot_mut(&mut self, slot: ItemSlot) -> Option<&mut Option<Item>> {
+        match slot {
+            ItemSlot::MainHand => Some(&mut self.mainhand),
+            ItemSlot::OffHand => Some(&mut self.offhand),
+            ItemSlot::Neck => Some(&mut self.neck),
+            ItemSlot::Head => Some(&mut self.head),
+            ItemSlot::Chest => Some(&mut self.chest),
+            ItemSlot::Legs => Some(&mut self.legs),
+            ItemSlot::Boots => Some(&mut self.boots),
+            ItemSlot::LeftFinger => Some(&mut self.leftfinger),
+            ItemSlot::RightFinger => Some(&mut self.rightfinger),
+            ItemSlot::Ammo => Some(&mut self.ammo),
+            ItemSlot::Consumable => None,
+        }
+    }
+
+    /// Total weight of what is worn.
+    pub fn weight(&self) -> f32 {
+        self.clone()
+            .into_iter()
+            .flatten()
+            .map(|item| item.total_weight())
+            .sum()
+    }
+
+    /// Range of the ranged weapon in the main hand, if any.
+    pub fn ranged_weapon(&self) -> Option<RangedWeapon> {
+        self.mainhand.as_ref().and_then(|item| item.ranged)
+    }
+
+    /// Whether the ranged weapon in the main hand has something to shoot, always true without
+    /// one.
+    pub fn has_ammunition(&self) -> bool {
+        match self.ranged_weapon().map(|weapon| weapon.ammo) {
+            None => true,
+            Some(None) => self.mainhand.is_some(),
+            Some(Some(ammo)) => self.ammo.as_ref().map_or(false, |item| item.ammo == Some(ammo)),
+        }
+    }
+
+    /// Uses up one piece of ammunition for the ranged weapon in the main hand, taken from the
+    /// ammo slot or, for thrown weapons, from the weapon's own stack. Returns false when there
+    /// is none left.
+    pub fn use_ammunition(&mut self) -> bool {
+        if !self.has_ammunition() {
+            return false;
+        }
+        let stack = match self.ranged_weapon().map(|weapon| weapon.ammo) {
+            None => return true,
+            Some(None) => &mut self.mainhand,
+            Some(Some(_)) => &mut self.ammo,
+        };
+        let emptied = match stack.as_mut() {
+            Some(item) => {
+                item.count -= 1;
+                item.count == 0
+            }
+            None => return false,
+        };
+        if emptied {
+            *stack = None;
+        }
+        true
+    }
+
+    pub fn get(&self, slot: ItemSlot) -> Option<&Item> {
+        match slot {
+            ItemSlot::MainHand => self.mainhand.as_ref(),
+            ItemSlot::OffHand => self.offhand.as_ref(),
+            ItemSlot::Neck => self.neck.as_ref(),
+            ItemSlot::Head => self.head.as_ref(),
+            ItemSlot::Chest => self.chest.as_ref(),
+            ItemSlot::Legs => self.legs.as_ref(),
+            ItemSlot::Boots => self.boots.as_ref(),
+            ItemSlot::LeftFinger => self.leftfinger.as_ref(),
+            ItemSlot::RightFinger => self.rightfinger.as_ref(),
+            ItemSlot::Ammo => self.ammo.as_ref(),
+            ItemSlot::Consumable => None,
+        }
+    }
+}
+
+impl ItemSlot {
+    /// Whether `item` can be worn in this slot, rings go on either finger.
+    pub fn accepts(self, item: &Item) -> bool {
+        match (self, item.slot) {
+            (ItemSlot::Consumable, _) => false,
+            (ItemSlot::LeftFinger, ItemSlot::RightFinger)
+            | (ItemSlot::RightFinger, ItemSlot::LeftFinger) => true,
+            (slot, item_slot) => slot == item_slot,
+        }
+    }
+}
+
 impl Default for Equipments {
     fn default() -> Self {
         Equipments {
@@ -111,6 +214,7 @@ impl Default for Equipments {
             boots: None,
             leftfinger: None,
             rightfinger: None,
+            ammo: None,
         }
     }
 }
@@ -146,6 +250,7 @@ impl Iterator for EquipmentsIntoIterator {
             6 => self.equipments.boots.clone(),
             7 => self.equipments.leftfinger.clone(),
             8 => self.equipments.rightfinger.clone(),
+            9 => self.equipments.ammo.clone(),
             _ => return None,
         };
         self.index += 1;
@@ -153,7 +258,7 @@ impl Iterator for EquipmentsIntoIterator {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ItemSlot {
     MainHand,
     OffHand,
@@ -164,19 +269,62 @@ pub enum ItemSlot {
     Boots,
     LeftFinger,
     RightFinger,
+    Ammo,
+    Consumable,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AmmoType {
+    Arrow,
+    Bolt,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RangedWeapon {
+    /// Tiles, replacing `Attack.range`.
+    pub range: f32,
+    /// Ammunition shot, `None` for thrown weapons which are their own ammunition.
+    pub ammo: Option<AmmoType>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Item {
     pub title: String,
     pub description: String,
     pub slot: ItemSlot,
     pub attributes: Vec<Attribute>,
+    #[serde(default)]
+    pub effect: Option<SpellEffect>,
+    /// Ounces.
+    #[serde(default)]
+    pub weight: f32,
+    /// Vocations that can wear or use it, everyone when empty.
+    #[serde(default)]
+    pub vocations: Vec<Vocation>,
+    /// Skill used and trained when fighting with it.
+    #[serde(default)]
+    pub skill: Option<Skill>,
+    #[serde(default)]
+    pub ranged: Option<RangedWeapon>,
+    /// Kind of ammunition this item is.
+    #[serde(default)]
+    pub ammo: Option<AmmoType>,
+    /// Stacked items like arrows, 1 for everything else.
+    #[serde(default = "single")]
+    pub count: u32,
+}
+
+fn single() -> u32 {
+    1
 }
 
 // TODO: ARRUMAR O ESQUEMA DE CRIAÇÃO DE ITEMS (COLETA DE DADOS)!
 
 impl Item {
+    pub fn total_weight(&self) -> f32 {
+        self.weight * self.count as f32
+    }
+
     pub fn new(title: &str) -> Option<Item> {
         match title {
             "Sword" => Some(Item {
@@ -187,6 +335,13 @@ impl Item {
                     value: 10.,
                     attribute_type: AttributeType::Damage(DamageType::Physical),
                 }],
+                effect: None,
+                weight: 35.,
+                vocations: vec![Vocation::Knight, Vocation::Paladin],
+                skill: Some(Skill::Sword),
+                ranged: None,
+                ammo: None,
+                count: 1,
             }),
             "Leather Armor" => Some(Item {
                 title: "Leather Armor".to_string(),
@@ -196,6 +351,13 @@ impl Item {
                     value: 10.,
                     attribute_type: AttributeType::Defense,
                 }],
+                effect: None,
+                weight: 120.,
+                vocations: vec![],
+                skill: None,
+                ranged: None,
+                ammo: None,
+                count: 1,
             }),
             "Wooden Shield" => Some(Item {
                 title: "Wooden Shield".to_string(),
@@ -205,19 +367,173 @@ impl Item {
                     value: 10.,
                     attribute_type: AttributeType::Resistance(DamageType::Physical),
                 }],
+                effect: None,
+                weight: 40.,
+                vocations: vec![Vocation::Knight, Vocation::Paladin],
+                skill: Some(Skill::Shielding),
+                ranged: None,
+                ammo: None,
+                count: 1,
+            }),
+            "Health Potion" => Some(Item {
+                title: "Health Potion".to_string(),
+                description: "Restores 50 health".to_string(),
+                slot: ItemSlot::Consumable,
+                attributes: vec![],
+                effect: Some(SpellEffect::Heal(50.)),
+                weight: 2.,
+                vocations: vec![],
+                skill: None,
+                ranged: None,
+                ammo: None,
+                count: 1,
+            }),
+            "Mana Potion" => Some(Item {
+                title: "Mana Potion".to_string(),
+                description: "Restores 50 mana".to_string(),
+                slot: ItemSlot::Consumable,
+                attributes: vec![],
+                effect: Some(SpellEffect::RestoreMana(50.)),
+                weight: 2.,
+                vocations: vec![],
+                skill: None,
+                ranged: None,
+                ammo: None,
+                count: 1,
+            }),
+            "Wand" => Some(Item {
+                title: "Wand".to_string(),
+                description: "Wand of embers".to_string(),
+                slot: ItemSlot::MainHand,
+                attributes: vec![Attribute {
+                    value: 8.,
+                    attribute_type: AttributeType::Damage(DamageType::Fire),
+                }],
+                effect: None,
+                weight: 19.,
+                vocations: vec![Vocation::Sorcerer],
+                skill: Some(Skill::MagicLevel),
+                ranged: None,
+                ammo: None,
+                count: 1,
+            }),
+            "Rod" => Some(Item {
+                title: "Rod".to_string(),
+                description: "Snakebite rod".to_string(),
+                slot: ItemSlot::MainHand,
+                attributes: vec![Attribute {
+                    value: 8.,
+                    attribute_type: AttributeType::Damage(DamageType::Earth),
+                }],
+                effect: None,
+                weight: 19.,
+                vocations: vec![Vocation::Druid],
+                skill: Some(Skill::MagicLevel),
+                ranged: None,
+                ammo: None,
+                count: 1,
+            }),
+            "Bow" => Some(Item {
+                title: "Bow".to_string(),
+                description: "Shoots arrows".to_string(),
+                slot: ItemSlot::MainHand,
+                attributes: vec![Attribute {
+                    value: 5.,
+                    attribute_type: AttributeType::Damage(DamageType::Physical),
+                }],
+                effect: None,
+                weight: 31.,
+                vocations: vec![Vocation::Paladin],
+                skill: Some(Skill::Distance),
+                ranged: Some(RangedWeapon {
+                    range: 5.,
+                    ammo: Some(AmmoType::Arrow),
+                }),
+                ammo: None,
+                count: 1,
+            }),
+            "Crossbow" => Some(Item {
+                title: "Crossbow".to_string(),
+                description: "Shoots bolts".to_string(),
+                slot: ItemSlot::MainHand,
+                attributes: vec![Attribute {
+                    value: 8.,
+                    attribute_type: AttributeType::Damage(DamageType::Physical),
+                }],
+                effect: None,
+                weight: 40.,
+                vocations: vec![Vocation::Paladin],
+                skill: Some(Skill::Distance),
+                ranged: Some(RangedWeapon {
+                    range: 6.,
+                    ammo: Some(AmmoType::Bolt),
+                }),
+                ammo: None,
+                count: 1,
+            }),
+            "Spear" => Some(Item {
+                title: "Spear".to_string(),
+                description: "Thrown at a distance".to_string(),
+                slot: ItemSlot::MainHand,
+                attributes: vec![Attribute {
+                    value: 12.,
+                    attribute_type: AttributeType::Damage(DamageType::Physical),
+                }],
+                effect: None,
+                weight: 20.,
+                vocations: vec![Vocation::Knight, Vocation::Paladin],
+                skill: Some(Skill::Distance),
+                ranged: Some(RangedWeapon {
+                    range: 3.,
+                    ammo: None,
+                }),
+                ammo: None,
+                count: 1,
+            }),
+            "Arrow" => Some(Item {
+                title: "Arrow".to_string(),
+                description: "Ammunition for bows".to_string(),
+                slot: ItemSlot::Ammo,
+                attributes: vec![Attribute {
+                    value: 5.,
+                    attribute_type: AttributeType::Damage(DamageType::Physical),
+                }],
+                effect: None,
+                weight: 0.7,
+                vocations: vec![],
+                skill: None,
+                ranged: None,
+                ammo: Some(AmmoType::Arrow),
+                count: 1,
+            }),
+            "Bolt" => Some(Item {
+                title: "Bolt".to_string(),
+                description: "Ammunition for crossbows".to_string(),
+                slot: ItemSlot::Ammo,
+                attributes: vec![Attribute {
+                    value: 8.,
+                    attribute_type: AttributeType::Damage(DamageType::Physical),
+                }],
+                effect: None,
+                weight: 0.9,
+                vocations: vec![],
+                skill: None,
+                ranged: None,
+                ammo: Some(AmmoType::Bolt),
+                count: 1,
             }),
             _ => None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Attribute {
     pub value: f32,
     pub attribute_type: AttributeType,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AttributeType {
     Damage(DamageType),
     Defense,
@@ -227,3 +543,37 @@ pub enum AttributeType {
     MaxHealth,
     MaxMana,
 }
+
+/// Ounces a player can carry, worn items included.
+#[derive(Debug, Clone, Copy)]
+pub struct Capacity(pub f32);
+
+impl Default for Capacity {
+    fn default() -> Self {
+        Capacity(400.)
+    }
+}
+
+pub struct GroundItem(pub Item);
+
+pub fn spawn_ground_item(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    item: Item,
+    translation: Vec3,
+    tile_size: f32,
+) -> Entity {
+    commands
+        .spawn()
+        .insert(GroundItem(item))
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                size: Vec2::new(tile_size / 2., tile_size / 2.),
+                ..Default::default()
+            },
+            material: materials.add(Color::ORANGE.into()),
+            transform: Transform::from_translation(translation),
+            ..Default::default()
+        })
+        .id()
+}
@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::read_ron;
+
+pub const LEVEL_CURVE_PATH: &str = "assets/levels.ron";
+
+/// Experience needed per level and how monster experience scales with levels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelCurve {
+    pub max_level: u32,
+    /// Experience to advance from level `i` to `i + 1`.
+    pub experience: Vec<u32>,
+    /// Multiplier change per level a monster is above (or below) its killer.
+    pub level_difference_factor: f32,
+    pub min_experience_scale: f32,
+    pub max_experience_scale: f32,
+}
+
+impl Default for LevelCurve {
+    fn default() -> Self {
+        LevelCurve {
+            max_level: 50,
+            experience: (0..50)
+                .map(|level| if level == 0 { 100 } else { 120 * level * level })
+                .collect(),
+            level_difference_factor: 0.1,
+            min_experience_scale: 0.1,
+            max_experience_scale: 1.5,
+        }
+    }
+}
+
+impl LevelCurve {
+    pub fn load(path: &Path) -> Self {
+        match read_ron::<LevelCurve>(path) {
+            Ok(mut curve) => {
+                if (curve.experience.len() as u32) < curve.max_level {
+                    println!(
+                        "Level curve {:?} stops at level {}, capping the max level",
+                        path,
+                        curve.experience.len()
+                    );
+                    curve.max_level = curve.experience.len() as u32;
+                }
+                curve
+            }
+            Err(e) => {
+                println!("Using the default level curve ({:?}): {:?}", path, e);
+                LevelCurve::default()
+            }
+        }
+    }
+
+    /// Experience to go from `level` to the next one, `None` at the max level.
+    pub fn experience_for(&self, level: u32) -> Option<u32> {
+        if level >= self.max_level {
+            return None;
+        }
+        self.experience.get(level as usize).copied()
+    }
+
+    /// Spends `experience` on as many level-ups from `level` as it pays for. Returns the new
+    /// level and the experience left, which is dropped at the max level.
+    pub fn advance(&self, mut level: u32, mut experience: u32) -> (u32, u32) {
+        while let Some(needed) = self.experience_for(level) {
+            if experience < needed {
+                return (level, experience);
+            }
+            experience -= needed;
+            level += 1;
+        }
+        (level.min(self.max_level), 0)
+    }
+
+    /// Multiplier for the experience of a `monster_level` monster killed at `killer_level`.
+    pub fn experience_scale(&self, killer_level: u32, monster_level: u32) -> f32 {
+        let difference = monster_level as f32 - killer_level as f32;
+        (1. + self.level_difference_factor * difference)
+            .max(self.min_experience_scale)
+            .min(self.max_experience_scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve() -> LevelCurve {
+        LevelCurve {
+            max_level: 3,
+            experience: vec![100, 200, 300],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn shipped_curve_matches_the_default() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(LEVEL_CURVE_PATH);
+        assert_eq!(
+            read_ron::<LevelCurve>(&path).unwrap(),
+            LevelCurve::default()
+        );
+    }
+
+    #[test]
+    fn default_curve_keeps_the_old_formula() {
+        let curve = LevelCurve::default();
+        assert_eq!(curve.experience_for(0), Some(100));
+        for level in 1..curve.max_level {
+            let old = level * (((level * 100) as f32 * 1.2) as u32);
+            assert_eq!(curve.experience_for(level), Some(old));
+        }
+    }
+
+    #[test]
+    fn no_experience_needed_past_the_max_level() {
+        assert_eq!(curve().experience_for(2), Some(300));
+        assert_eq!(curve().experience_for(3), None);
+        assert_eq!(curve().experience_for(10), None);
+    }
+
+    #[test]
+    fn advance_processes_several_levels_at_once() {
+        assert_eq!(curve().advance(0, 99), (0, 99));
+        assert_eq!(curve().advance(0, 100), (1, 0));
+        assert_eq!(curve().advance(0, 350), (2, 50));
+        assert_eq!(curve().advance(1, 250), (2, 50));
+    }
+
+    #[test]
+    fn advance_stops_at_the_max_level() {
+        assert_eq!(curve().advance(0, 600), (3, 0));
+        assert_eq!(curve().advance(0, 10_000), (3, 0));
+        assert_eq!(curve().advance(3, 500), (3, 0));
+    }
+
+    #[test]
+    fn experience_scales_with_the_level_difference() {
+        let curve = curve();
+        assert_eq!(curve.experience_scale(5, 5), 1.);
+        assert!((curve.experience_scale(5, 7) - 1.2).abs() < 1e-6);
+        assert!((curve.experience_scale(7, 5) - 0.8).abs() < 1e-6);
+        assert_eq!(curve.experience_scale(1, 50), curve.max_experience_scale);
+        assert_eq!(curve.experience_scale(50, 1), curve.min_experience_scale);
+    }
+}
@@ -0,0 +1,218 @@
+pub mod ai;
+pub mod character;
+pub mod combat;
+pub mod config;
+pub mod console;
+pub mod entities;
+pub mod hotbar;
+pub mod hud;
+pub mod input;
+pub mod input_map;
+pub mod inventory;
+pub mod item;
+pub mod items;
+pub mod levels;
+pub mod map;
+pub mod messages;
+pub mod minimap;
+pub mod network;
+pub mod party;
+pub mod settings;
+pub mod skills;
+pub mod snapshot;
+pub mod spell;
+pub mod states;
+pub mod visibility;
+pub mod vocation;
+
+use std::time::Duration;
+
+use bevy::{
+    app::PluginGroupBuilder,
+    prelude::*,
+    render::{pipeline::RenderPipeline, render_graph::base::MainPass},
+    sprite::{QUAD_HANDLE, SPRITE_PIPELINE_HANDLE},
+};
+
+use crate::{
+    ai::{spawn_monster, AiPlugin, SpawnPoint},
+    combat::CombatPlugin,
+    entities::{spawn_player, EntityPlugin},
+    input::{MovementPlugin, KEYBOARD_SLOT},
+    item::{spawn_ground_item, Item},
+    map::{spawn_wall, MapPlugin, TileMap, TilePos},
+    messages::MessagePlugin,
+    network::{NetworkMode, NetworkPlugin},
+    party::PartyPlugin,
+    skills::SkillsPlugin,
+    snapshot::{LoadSnapshotEvent, SnapshotPlugin, StartupSnapshot},
+    spell::SpellPlugin,
+    states::AppState,
+    vocation::{NewCharacter, VocationPlugin},
+};
+
+/// Plugins simulating the world, shared by the game and the dedicated server. Everything drawn
+/// or read from the keyboard and mouse is added by the game on top of these.
+pub struct SimulationPlugins;
+
+impl PluginGroup for SimulationPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(WorldPlugin)
+            .add(MapPlugin)
+            .add(MovementPlugin)
+            .add(CombatPlugin)
+            .add(EntityPlugin)
+            .add(AiPlugin)
+            .add(SnapshotPlugin)
+            .add(SpellPlugin)
+            .add(PartyPlugin)
+            .add(VocationPlugin)
+            .add(SkillsPlugin)
+            .add(MessagePlugin)
+            .add(NetworkPlugin);
+    }
+}
+
+pub struct WorldPlugin;
+
+impl Plugin for WorldPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<LocalPlayer>().add_system_set(
+            SystemSet::on_enter(AppState::Playing).with_system(setup_world.system()),
+        );
+    }
+}
+
+/// The player this machine presents: camera, HUD, panels and messages. Gameplay acts on
+/// every `Player`, this is `None` on a server and until a local player is spawned.
+#[derive(Default)]
+pub struct LocalPlayer(pub Option<Entity>);
+
+pub struct HealthManaBar;
+pub struct Bars;
+pub struct HealthBar;
+pub struct ManaBar;
+
+#[derive(Bundle)]
+struct HealthManaBarBundle {
+    pub sprite: Sprite,
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<ColorMaterial>,
+    pub main_pass: MainPass,
+    pub draw: Draw,
+    pub visible: Visible,
+    pub render_pipelines: RenderPipelines,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub health_mana_bar: Bars,
+}
+impl Default for HealthManaBarBundle {
+    fn default() -> Self {
+        Self {
+            mesh: QUAD_HANDLE.typed(),
+            render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                SPRITE_PIPELINE_HANDLE.typed(),
+            )]),
+            visible: Visible {
+                is_transparent: true,
+                ..Default::default()
+            },
+            main_pass: MainPass,
+            draw: Default::default(),
+            sprite: Default::default(),
+            material: Default::default(),
+            transform: Transform::from_xyz(0., 0., 7.),
+            global_transform: Default::default(),
+            health_mana_bar: Bars,
+        }
+    }
+}
+
+/// Spawns the starting world, or the `--snapshot` one. Clients only get the walls and mirror
+/// the rest from the server.
+fn setup_world(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut localplayer: ResMut<LocalPlayer>,
+    tile_map: Res<TileMap>,
+    startup_snapshot: Res<StartupSnapshot>,
+    network: Res<NetworkMode>,
+    character: Res<NewCharacter>,
+    mut load_events: EventWriter<LoadSnapshotEvent>,
+) {
+    let size = tile_map.tile_size;
+    for y in -2..=2 {
+        spawn_wall(&mut commands, &mut materials, TilePos::new(3, y), size);
+    }
+
+    // Everything else comes from the server.
+    if !network.is_authoritative() {
+        return;
+    }
+
+    if let Some(path) = &startup_snapshot.0 {
+        load_events.send(LoadSnapshotEvent(path.clone()));
+        return;
+    }
+
+    // Players on a server join over the network.
+    if !network.is_server() {
+        // #Ent 1
+        let player = spawn_player(
+            &mut commands,
+            &mut materials,
+            "Demnok",
+            character.vocation,
+            Vec3::new(0., 0., 0.),
+            size,
+            KEYBOARD_SLOT,
+        );
+        localplayer.0 = Some(player);
+    }
+
+    // #Ent 2
+    let monster = spawn_monster(
+        &mut commands,
+        &mut materials,
+        "#1",
+        TilePos::new(1, 0).to_world(size, 0.),
+        size,
+    );
+    commands.spawn().insert(SpawnPoint {
+        spawned: Some(monster),
+        ..SpawnPoint::new(
+            "#1",
+            TilePos::new(1, 0).to_world(size, 0.),
+            Duration::from_secs(30),
+        )
+    });
+
+    // #Ent 3
+    spawn_monster(
+        &mut commands,
+        &mut materials,
+        "#2",
+        TilePos::new(-1, 0).to_world(size, 0.),
+        size,
+    );
+
+    // # Ent 4
+    spawn_monster(
+        &mut commands,
+        &mut materials,
+        "#3",
+        TilePos::new(-1, 1).to_world(size, 0.),
+        size,
+    );
+
+    if let Some(item) = Item::new("Leather Armor") {
+        spawn_ground_item(
+            &mut commands,
+            &mut materials,
+            item,
+            TilePos::new(0, -2).to_world(size, 0.),
+            size,
+        );
+    }
+}
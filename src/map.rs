@@ -0,0 +1,229 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{states::AppState, visibility::visible_tiles};
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<TileMap>().add_system_set(
+            SystemSet::on_update(AppState::Playing)
+                .with_system(index_walls.system())
+                .with_system(explore_tiles.system()),
+        );
+    }
+}
+
+/// Side of a tile in world units for worlds that don't set their own `TileMap::tile_size`.
+pub const DEFAULT_TILE_SIZE: f32 = 32.;
+
+/// Tiles around a player it can see, revealed on its map as it walks.
+pub const EXPLORE_RADIUS: i32 = 6;
+
+/// Upper bound of tiles expanded by `find_path`, the world has no edges to stop the search.
+const MAX_PATH_NODES: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct TilePos {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl TilePos {
+    pub fn new(x: i32, y: i32) -> Self {
+        TilePos { x, y }
+    }
+
+    pub fn from_world(translation: Vec3, tile_size: f32) -> Self {
+        TilePos {
+            x: (translation.x / tile_size).round() as i32,
+            y: (translation.y / tile_size).round() as i32,
+        }
+    }
+
+    pub fn to_world(self, tile_size: f32, z: f32) -> Vec3 {
+        Vec3::new(self.x as f32 * tile_size, self.y as f32 * tile_size, z)
+    }
+
+    pub fn offset(self, delta: IVec2) -> Self {
+        TilePos::new(self.x + delta.x, self.y + delta.y)
+    }
+
+    pub fn delta(self, other: TilePos) -> IVec2 {
+        IVec2::new(other.x - self.x, other.y - self.y)
+    }
+
+    /// Distance in world units, comparable with ranges like `Attack.range`.
+    pub fn world_distance(self, other: TilePos, tile_size: f32) -> f32 {
+        self.to_world(tile_size, 0.)
+            .distance(other.to_world(tile_size, 0.))
+    }
+
+    pub fn neighbours(self) -> [TilePos; 8] {
+        [
+            TilePos::new(self.x, self.y + 1),
+            TilePos::new(self.x, self.y - 1),
+            TilePos::new(self.x - 1, self.y),
+            TilePos::new(self.x + 1, self.y),
+            TilePos::new(self.x - 1, self.y + 1),
+            TilePos::new(self.x + 1, self.y + 1),
+            TilePos::new(self.x - 1, self.y - 1),
+            TilePos::new(self.x + 1, self.y - 1),
+        ]
+    }
+
+    /// Octile distance: 10 per straight step and 14 per diagonal one.
+    fn step_cost(self, other: TilePos) -> u32 {
+        let dx = (self.x - other.x).abs() as u32;
+        let dy = (self.y - other.y).abs() as u32;
+        14 * dx.min(dy) + 10 * (dx.max(dy) - dx.min(dy))
+    }
+}
+
+pub struct Wall;
+
+/// Wall tiles, indexed once when the `Wall` is spawned.
+#[derive(Debug)]
+pub struct TileMap {
+    pub walls: HashSet<TilePos>,
+    /// Side of a tile in world units, every position in the world sits on this grid.
+    pub tile_size: f32,
+}
+
+impl Default for TileMap {
+    fn default() -> Self {
+        TileMap {
+            walls: HashSet::new(),
+            tile_size: DEFAULT_TILE_SIZE,
+        }
+    }
+}
+
+impl TileMap {
+    pub fn is_wall(&self, tile: TilePos) -> bool {
+        self.walls.contains(&tile)
+    }
+}
+
+/// Tiles a player has seen, drawn on its minimap.
+#[derive(Debug, Default, Clone)]
+pub struct ExploredTiles(pub HashSet<TilePos>);
+
+fn explore_tiles(
+    tile_map: Res<TileMap>,
+    mut query: Query<(&TilePos, &mut ExploredTiles), Changed<TilePos>>,
+) {
+    for (tile, mut explored) in query.iter_mut() {
+        explored
+            .0
+            .extend(visible_tiles(&tile_map, *tile, EXPLORE_RADIUS));
+    }
+}
+
+pub fn spawn_wall(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    tile: TilePos,
+    tile_size: f32,
+) -> Entity {
+    commands
+        .spawn()
+        .insert(Wall)
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                size: Vec2::new(tile_size, tile_size),
+                ..Default::default()
+            },
+            material: materials.add(Color::DARK_GRAY.into()),
+            transform: Transform::from_translation(tile.to_world(tile_size, 0.)),
+            ..Default::default()
+        })
+        .id()
+}
+
+fn index_walls(mut tile_map: ResMut<TileMap>, walls: Query<&Transform, Added<Wall>>) {
+    let tile_size = tile_map.tile_size;
+    for transform in walls.iter() {
+        tile_map
+            .walls
+            .insert(TilePos::from_world(transform.translation, tile_size));
+    }
+}
+
+/// Nearest tile to `center` that is not `blocked`, searched ring by ring up to `radius` tiles
+/// away.
+pub fn nearest_free_tile(
+    center: TilePos,
+    blocked: &HashSet<TilePos>,
+    radius: i32,
+) -> Option<TilePos> {
+    (0..=radius).find_map(|r| {
+        (-r..=r)
+            .flat_map(|x| (-r..=r).map(move |y| TilePos::new(center.x + x, center.y + y)))
+            .filter(|tile| (tile.x - center.x).abs() == r || (tile.y - center.y).abs() == r)
+            .find(|tile| !blocked.contains(tile))
+    })
+}
+
+/// A* over the eight neighbours of each tile. The returned path excludes `start` and ends at
+/// `goal`, or is `None` when the goal is blocked or unreachable.
+pub fn find_path(
+    start: TilePos,
+    goal: TilePos,
+    blocked: &HashSet<TilePos>,
+) -> Option<Vec<TilePos>> {
+    if start == goal {
+        return Some(vec![]);
+    }
+    if blocked.contains(&goal) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<TilePos, TilePos> = HashMap::new();
+    let mut costs: HashMap<TilePos, u32> = HashMap::new();
+    costs.insert(start, 0);
+    open.push(Reverse((start.step_cost(goal), start)));
+
+    let mut expanded = 0;
+    while let Some(Reverse((_, current))) = open.pop() {
+        if current == goal {
+            let mut path = vec![current];
+            let mut tile = current;
+            while let Some(previous) = came_from.get(&tile) {
+                if *previous == start {
+                    break;
+                }
+                path.push(*previous);
+                tile = *previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        expanded += 1;
+        if expanded > MAX_PATH_NODES {
+            return None;
+        }
+
+        let cost = costs[&current];
+        for next in current.neighbours().iter() {
+            if blocked.contains(next) {
+                continue;
+            }
+            let next_cost = cost + current.step_cost(*next);
+            if costs.get(next).map_or(true, |c| next_cost < *c) {
+                costs.insert(*next, next_cost);
+                came_from.insert(*next, current);
+                open.push(Reverse((next_cost + next.step_cost(goal), *next)));
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,407 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use bevy::{input::mouse::MouseWheel, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    console::{ConsoleAppExt, ConsoleCommand, ConsoleOutput},
+    LocalPlayer,
+};
+
+pub const MESSAGE_LOG_PATH: &str = "logs/messages.log";
+/// Messages kept in memory, older ones are only in the log file.
+const MAX_MESSAGES: usize = 200;
+const VISIBLE_MESSAGES: usize = 8;
+
+pub struct MessagePlugin;
+
+impl Plugin for MessagePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<GameMessage>()
+            .add_event::<PlayerMessage>()
+            .add_system(local_player_messages.system());
+    }
+}
+
+/// The message log window and its file.
+pub struct MessageUiPlugin;
+
+impl Plugin for MessageUiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<MessageLog>()
+            .insert_resource(MessageFile::open(Path::new(MESSAGE_LOG_PATH)))
+            .add_console_command(
+                "filter",
+                "/filter <combat|loot|party|system|error>",
+                "Shows or hides a channel in the All tab of the message log.",
+            )
+            .add_startup_system(setup.system())
+            .add_system(collect_messages.system())
+            .add_system(filter_command.system().after("console"))
+            .add_system(message_tabs.system())
+            .add_system(scroll_messages.system())
+            .add_system(message_log_text.system());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MessageChannel {
+    Combat,
+    Loot,
+    Party,
+    System,
+    Error,
+}
+
+pub const MESSAGE_CHANNELS: [MessageChannel; 5] = [
+    MessageChannel::Combat,
+    MessageChannel::Loot,
+    MessageChannel::Party,
+    MessageChannel::System,
+    MessageChannel::Error,
+];
+
+impl MessageChannel {
+    pub fn label(self) -> &'static str {
+        match self {
+            MessageChannel::Combat => "Combat",
+            MessageChannel::Loot => "Loot",
+            MessageChannel::Party => "Party",
+            MessageChannel::System => "System",
+            MessageChannel::Error => "Error",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            MessageChannel::Combat => Color::WHITE,
+            MessageChannel::Loot => Color::GOLD,
+            MessageChannel::Party => Color::CYAN,
+            MessageChannel::System => Color::GREEN,
+            MessageChannel::Error => Color::ORANGE_RED,
+        }
+    }
+}
+
+/// A line for the message log, sent by gameplay systems instead of printing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameMessage {
+    pub channel: MessageChannel,
+    pub text: String,
+}
+
+impl GameMessage {
+    pub fn combat(text: impl Into<String>) -> Self {
+        GameMessage {
+            channel: MessageChannel::Combat,
+            text: text.into(),
+        }
+    }
+
+    pub fn loot(text: impl Into<String>) -> Self {
+        GameMessage {
+            channel: MessageChannel::Loot,
+            text: text.into(),
+        }
+    }
+
+    pub fn party(text: impl Into<String>) -> Self {
+        GameMessage {
+            channel: MessageChannel::Party,
+            text: text.into(),
+        }
+    }
+
+    pub fn system(text: impl Into<String>) -> Self {
+        GameMessage {
+            channel: MessageChannel::System,
+            text: text.into(),
+        }
+    }
+
+    pub fn error(text: impl Into<String>) -> Self {
+        GameMessage {
+            channel: MessageChannel::Error,
+            text: text.into(),
+        }
+    }
+}
+
+/// A message meant for one player: logged here when it is the `LocalPlayer`, sent to its
+/// client by a server.
+#[derive(Debug)]
+pub struct PlayerMessage(pub Entity, pub GameMessage);
+
+#[derive(Debug, Default)]
+pub struct MessageLog {
+    pub messages: VecDeque<GameMessage>,
+    /// Selected tab, `None` for all channels.
+    pub tab: Option<MessageChannel>,
+    /// Channels left out of the All tab.
+    pub hidden: HashSet<MessageChannel>,
+    /// Messages scrolled up from the newest one.
+    pub scroll: usize,
+}
+
+impl MessageLog {
+    pub fn shown(&self) -> Vec<&GameMessage> {
+        self.messages
+            .iter()
+            .filter(|message| match self.tab {
+                Some(channel) => message.channel == channel,
+                None => !self.hidden.contains(&message.channel),
+            })
+            .collect()
+    }
+}
+
+/// Append-only mirror of every message, for debugging.
+pub struct MessageFile(Option<File>);
+
+impl MessageFile {
+    fn open(path: &Path) -> Self {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => MessageFile(Some(file)),
+            Err(e) => {
+                println!("Failed to open message log {:?}: {:?}", path, e);
+                MessageFile(None)
+            }
+        }
+    }
+}
+
+pub struct MessageLogWindow;
+
+pub struct MessageLogText;
+
+pub struct MessageTab(pub Option<MessageChannel>);
+
+pub struct TabMaterials {
+    normal: Handle<ColorMaterial>,
+    selected: Handle<ColorMaterial>,
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font = asset_server.load("fonts/font.ttf");
+    let tab_materials = TabMaterials {
+        normal: materials.add(Color::rgba(0.1, 0.1, 0.1, 0.9).into()),
+        selected: materials.add(Color::rgba(0.4, 0.4, 0.4, 0.9).into()),
+    };
+    let tabs = std::iter::once(None).chain(MESSAGE_CHANNELS.iter().map(|c| Some(*c)));
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(45.),
+                    left: Val::Px(5.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(360.), Val::Px(140.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(4.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0., 0., 0., 0.5).into()),
+            ..Default::default()
+        })
+        .insert(Interaction::default())
+        .insert(MessageLogWindow)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::NONE.into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for tab in tabs {
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    margin: Rect::all(Val::Px(1.)),
+                                    padding: Rect::all(Val::Px(2.)),
+                                    ..Default::default()
+                                },
+                                material: if tab.is_none() {
+                                    tab_materials.selected.clone()
+                                } else {
+                                    tab_materials.normal.clone()
+                                },
+                                ..Default::default()
+                            })
+                            .insert(Interaction::default())
+                            .insert(MessageTab(tab))
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        tab.map_or("All", |c| c.label()),
+                                        TextStyle {
+                                            font: font.clone(),
+                                            font_size: 10.0,
+                                            color: Color::WHITE,
+                                        },
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            });
+                    }
+                });
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(MessageLogText);
+        });
+    commands.insert_resource(tab_materials);
+}
+
+fn collect_messages(
+    mut events: EventReader<GameMessage>,
+    mut log: ResMut<MessageLog>,
+    mut file: ResMut<MessageFile>,
+) {
+    for message in events.iter() {
+        if let Some(f) = file.0.as_mut() {
+            let _ = writeln!(f, "[{}] {}", message.channel.label(), message.text);
+        }
+        log.messages.push_back(message.clone());
+        if log.messages.len() > MAX_MESSAGES {
+            log.messages.pop_front();
+        }
+    }
+}
+
+fn local_player_messages(
+    player: Res<LocalPlayer>,
+    mut events: EventReader<PlayerMessage>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    for PlayerMessage(entity, message) in events.iter() {
+        if player.0 == Some(*entity) {
+            messages.send(message.clone());
+        }
+    }
+}
+
+fn filter_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    mut log: ResMut<MessageLog>,
+) {
+    for command in console_commands.iter().filter(|c| c.name == "filter") {
+        let channel = command.args.first().and_then(|arg| {
+            MESSAGE_CHANNELS
+                .iter()
+                .find(|c| c.label().eq_ignore_ascii_case(arg))
+        });
+        match channel {
+            Some(channel) => {
+                let was_shown = !log.hidden.remove(channel);
+                if was_shown {
+                    log.hidden.insert(*channel);
+                }
+                output.send(ConsoleOutput(format!(
+                    "{} messages {} in the All tab.",
+                    channel.label(),
+                    if was_shown { "hidden" } else { "shown" }
+                )));
+            }
+            None => output.send(ConsoleOutput(
+                "Usage: /filter <combat|loot|party|system|error>".to_string(),
+            )),
+        }
+    }
+}
+
+fn message_tabs(
+    mut log: ResMut<MessageLog>,
+    tab_materials: Res<TabMaterials>,
+    clicked: Query<(&Interaction, &MessageTab), Changed<Interaction>>,
+    mut tabs: Query<(&MessageTab, &mut Handle<ColorMaterial>)>,
+) {
+    for (interaction, tab) in clicked.iter() {
+        if *interaction != Interaction::Clicked || log.tab == tab.0 {
+            continue;
+        }
+        log.tab = tab.0;
+        log.scroll = 0;
+        for (tab, mut material) in tabs.iter_mut() {
+            *material = if tab.0 == log.tab {
+                tab_materials.selected.clone()
+            } else {
+                tab_materials.normal.clone()
+            };
+        }
+    }
+}
+
+fn scroll_messages(
+    mut wheel: EventReader<MouseWheel>,
+    mut log: ResMut<MessageLog>,
+    windows: Query<&Interaction, With<MessageLogWindow>>,
+) {
+    let hovered = windows
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    for event in wheel.iter() {
+        if !hovered {
+            continue;
+        }
+        let max_scroll = log.shown().len().saturating_sub(VISIBLE_MESSAGES);
+        log.scroll = if event.y > 0. {
+            (log.scroll + 1).min(max_scroll)
+        } else {
+            log.scroll.saturating_sub(1)
+        };
+    }
+}
+
+fn message_log_text(
+    log: Res<MessageLog>,
+    asset_server: Res<AssetServer>,
+    mut texts: Query<&mut Text, With<MessageLogText>>,
+) {
+    if !log.is_changed() {
+        return;
+    }
+    let shown = log.shown();
+    let end = shown.len().saturating_sub(log.scroll);
+    let start = end.saturating_sub(VISIBLE_MESSAGES);
+    let font = asset_server.load("fonts/font.ttf");
+    let sections: Vec<TextSection> = shown[start..end]
+        .iter()
+        .map(|message| TextSection {
+            value: format!("{}\n", message.text),
+            style: TextStyle {
+                font: font.clone(),
+                font_size: 11.0,
+                color: message.channel.color(),
+            },
+        })
+        .collect();
+    for mut text in texts.iter_mut() {
+        text.sections = sections.clone();
+    }
+}
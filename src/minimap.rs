@@ -0,0 +1,286 @@
+use bevy::{
+    prelude::*,
+    render::texture::{Extent3d, FilterMode, Texture, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    ai::Monster,
+    entities::Player,
+    input_map::{Action, Actions},
+    map::{ExploredTiles, TileMap, TilePos},
+    party::PartyMember,
+    states::AppState,
+    LocalPlayer,
+};
+
+/// Side of the corner minimap in pixels, two pixels per tile.
+const MINIMAP_SIZE: u32 = 128;
+const MINIMAP_SCALE: u32 = 2;
+/// Side of the large map view, zoomed from 1 to `MAX_ZOOM` pixels per tile.
+const LARGE_MAP_SIZE: u32 = 384;
+const MAX_ZOOM: u32 = 8;
+/// Monsters further than this many tiles are left off the map.
+const MONSTER_RADIUS: i32 = 10;
+const REDRAW_INTERVAL: f32 = 0.1;
+
+const UNEXPLORED_COLOR: [u8; 4] = [0, 0, 0, 255];
+const GROUND_COLOR: [u8; 4] = [70, 55, 40, 255];
+const WALL_COLOR: [u8; 4] = [150, 150, 150, 255];
+const MONSTER_COLOR: [u8; 4] = [220, 40, 40, 255];
+const ALLY_COLOR: [u8; 4] = [40, 200, 40, 255];
+const PLAYER_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<MapView>()
+            .add_startup_system(setup.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(map_view_controls.system().after("actions")),
+            )
+            .add_system(draw_maps.system());
+    }
+}
+
+/// State of the large map; the corner minimap always follows the player.
+#[derive(Debug)]
+pub struct MapView {
+    pub open: bool,
+    /// Pixels per tile.
+    pub zoom: u32,
+    /// Tiles between the player and the centre of the view.
+    pub pan: IVec2,
+    redraw: Timer,
+}
+
+impl Default for MapView {
+    fn default() -> Self {
+        MapView {
+            open: false,
+            zoom: 2,
+            pan: IVec2::default(),
+            redraw: Timer::from_seconds(REDRAW_INTERVAL, true),
+        }
+    }
+}
+
+pub struct Minimap(Handle<Texture>);
+
+pub struct LargeMap(Handle<Texture>);
+
+fn map_texture(size: u32) -> Texture {
+    let mut texture = Texture::new_fill(
+        Extent3d::new(size, size, 1),
+        TextureDimension::D2,
+        &UNEXPLORED_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    texture.sampler.mag_filter = FilterMode::Nearest;
+    texture.sampler.min_filter = FilterMode::Nearest;
+    texture
+}
+
+fn setup(
+    mut commands: Commands,
+    mut textures: ResMut<Assets<Texture>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let minimap = textures.add(map_texture(MINIMAP_SIZE));
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.),
+                    right: Val::Px(5.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(MINIMAP_SIZE as f32), Val::Px(MINIMAP_SIZE as f32)),
+                ..Default::default()
+            },
+            material: materials.add(ColorMaterial::texture(minimap.clone())),
+            ..Default::default()
+        })
+        .insert(Minimap(minimap));
+
+    let large_map = textures.add(map_texture(LARGE_MAP_SIZE));
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(60.),
+                    left: Val::Percent(25.),
+                    ..Default::default()
+                },
+                size: Size::new(
+                    Val::Px(LARGE_MAP_SIZE as f32),
+                    Val::Px(LARGE_MAP_SIZE as f32),
+                ),
+                ..Default::default()
+            },
+            material: materials.add(ColorMaterial::texture(large_map.clone())),
+            ..Default::default()
+        })
+        .insert(Interaction::default())
+        .insert(LargeMap(large_map));
+}
+
+fn map_view_controls(
+    actions: Res<Actions>,
+    mut view: ResMut<MapView>,
+    mut large_maps: Query<&mut Style, With<LargeMap>>,
+) {
+    if actions.just_pressed(Action::ToggleMap) {
+        view.open = !view.open;
+        view.pan = IVec2::default();
+        for mut style in large_maps.iter_mut() {
+            style.display = if view.open {
+                Display::Flex
+            } else {
+                Display::None
+            };
+        }
+    }
+    if !view.open {
+        return;
+    }
+    if actions.just_pressed(Action::MapZoomIn) {
+        view.zoom = (view.zoom + 1).min(MAX_ZOOM);
+    }
+    if actions.just_pressed(Action::MapZoomOut) {
+        view.zoom = (view.zoom - 1).max(1);
+    }
+    let pans = [
+        (Action::MapPanUp, IVec2::new(0, 1)),
+        (Action::MapPanDown, IVec2::new(0, -1)),
+        (Action::MapPanLeft, IVec2::new(-1, 0)),
+        (Action::MapPanRight, IVec2::new(1, 0)),
+    ];
+    // Pans by a quarter of the visible width per press.
+    let step = (LARGE_MAP_SIZE / view.zoom / 4) as i32;
+    for (action, direction) in pans.iter() {
+        if actions.just_pressed(*action) {
+            view.pan += *direction * step;
+        }
+    }
+}
+
+/// Draws the tiles around `center` into `texture`, `scale` pixels per tile.
+fn draw_map(
+    texture: &mut Texture,
+    center: TilePos,
+    scale: u32,
+    explored: &ExploredTiles,
+    tile_map: &TileMap,
+    dots: &[(TilePos, [u8; 4])],
+) {
+    let size = texture.size.width;
+    let tiles = (size / scale) as i32;
+    let origin = center.offset(IVec2::new(-tiles / 2, -tiles / 2));
+    let pixel_of = |tile: TilePos| {
+        let delta = origin.delta(tile);
+        if delta.x < 0 || delta.y < 0 || delta.x >= tiles || delta.y >= tiles {
+            None
+        } else {
+            Some((delta.x as u32, delta.y as u32))
+        }
+    };
+    let fill = |data: &mut Vec<u8>, (x, y): (u32, u32), color: &[u8; 4]| {
+        for px in x * scale..(x + 1) * scale {
+            for py in y * scale..(y + 1) * scale {
+                // Texture rows go top to bottom, tiles bottom to top.
+                let i = (((size - 1 - py) * size + px) * 4) as usize;
+                data[i..i + 4].copy_from_slice(color);
+            }
+        }
+    };
+
+    // Clears the edge left over when the size is not a multiple of the scale.
+    for pixel in texture.data.chunks_mut(4) {
+        pixel.copy_from_slice(&UNEXPLORED_COLOR);
+    }
+    for x in 0..tiles {
+        for y in 0..tiles {
+            let tile = origin.offset(IVec2::new(x, y));
+            let color = if !explored.0.contains(&tile) {
+                &UNEXPLORED_COLOR
+            } else if tile_map.is_wall(tile) {
+                &WALL_COLOR
+            } else {
+                &GROUND_COLOR
+            };
+            fill(&mut texture.data, (x as u32, y as u32), color);
+        }
+    }
+    for (tile, color) in dots.iter() {
+        if let Some(pixel) = pixel_of(*tile) {
+            fill(&mut texture.data, pixel, color);
+        }
+    }
+}
+
+fn draw_maps(
+    time: Res<Time>,
+    mut view: ResMut<MapView>,
+    player: Res<LocalPlayer>,
+    tile_map: Res<TileMap>,
+    mut textures: ResMut<Assets<Texture>>,
+    players: Query<(Entity, &TilePos, Option<&PartyMember>), With<Player>>,
+    explorers: Query<&ExploredTiles>,
+    monsters: Query<&TilePos, With<Monster>>,
+    minimaps: Query<&Minimap>,
+    large_maps: Query<&LargeMap>,
+) {
+    if !view.redraw.tick(time.delta()).just_finished() {
+        return;
+    }
+    let player = match player.0 {
+        Some(player) => player,
+        None => return,
+    };
+    let (center, party, explored) = match (players.get(player), explorers.get(player)) {
+        (Ok((_, tile, party)), Ok(explored)) => {
+            (*tile, party.map(|member| member.leader), explored)
+        }
+        _ => return,
+    };
+
+    let mut dots: Vec<(TilePos, [u8; 4])> = monsters
+        .iter()
+        .filter(|tile| {
+            let delta = center.delta(**tile);
+            delta.x.abs() <= MONSTER_RADIUS
+                && delta.y.abs() <= MONSTER_RADIUS
+                && explored.0.contains(*tile)
+        })
+        .map(|tile| (*tile, MONSTER_COLOR))
+        .collect();
+    // Only party members are shown, other players are not allies.
+    dots.extend(
+        players
+            .iter()
+            .filter(|(entity, _, member)| {
+                *entity != player && party.is_some() && member.map(|member| member.leader) == party
+            })
+            .map(|(_, tile, _)| (*tile, ALLY_COLOR)),
+    );
+    dots.push((center, PLAYER_COLOR));
+
+    for minimap in minimaps.iter() {
+        if let Some(texture) = textures.get_mut(&minimap.0) {
+            draw_map(texture, center, MINIMAP_SCALE, explored, &tile_map, &dots);
+        }
+    }
+    if view.open {
+        for large_map in large_maps.iter() {
+            if let Some(texture) = textures.get_mut(&large_map.0) {
+                let view_center = center.offset(view.pan);
+                draw_map(texture, view_center, view.zoom, explored, &tile_map, &dots);
+            }
+        }
+    }
+}
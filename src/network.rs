@@ -0,0 +1,1143 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use bevy::{
+    app::{ScheduleRunnerPlugin, ScheduleRunnerSettings},
+    audio::AudioPlugin,
+    prelude::*,
+    render::renderer::{HeadlessRenderResourceContext, RenderResourceContext},
+    wgpu::WgpuPlugin,
+    winit::WinitPlugin,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    ai::spawn_monster,
+    combat::{create_combat_text, DamageEvent, Health, LockedTarget, Mana},
+    entities::{spawn_player, spawn_player_body, Body, Level, Name, Player},
+    hotbar::{prepare_cast, Cooldowns, Hotbar, HotbarEntry},
+    input::{MoveAnimation, MoveEvent, PlayerInput, KEYBOARD_SLOT},
+    input_map::{Action, Actions},
+    item::Item,
+    map::{nearest_free_tile, TileMap, TilePos},
+    messages::{GameMessage, PlayerMessage},
+    party::{PartyCommand, PartyEvent, PartyMember},
+    spell::CastEvent,
+    states::AppState,
+    vocation::{NewCharacter, Vocation},
+    LocalPlayer,
+};
+
+pub const DEFAULT_PORT: u16 = 5000;
+/// Seconds between two world states sent by the server.
+const NETWORK_TICK: f32 = 0.05;
+/// Seconds between two keep-alive messages from a client, which also retries joining.
+const PING_INTERVAL: f32 = 1.;
+/// Seconds of silence after which the server drops a client.
+const CLIENT_TIMEOUT: f64 = 10.;
+const MAX_DATAGRAM: usize = 65_507;
+/// Room left in a `State` datagram for everything around its entities.
+const STATE_OVERHEAD: usize = 256;
+/// Players a server takes, further joins are turned away.
+const MAX_CLIENTS: usize = 16;
+const MAX_NAME_LENGTH: usize = 24;
+/// Tiles around the origin searched for a free tile to put a joining player on.
+const SPAWN_RADIUS: i32 = 10;
+
+/// Whether this instance plays alone, hosts other players or joins a host.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkMode {
+    Offline,
+    /// The dedicated `server` binary, `[--address <address>]`.
+    Server(SocketAddr),
+    /// `--connect <address> [--name <name>]`.
+    Client {
+        server: SocketAddr,
+        name: String,
+    },
+}
+
+impl Default for NetworkMode {
+    fn default() -> Self {
+        NetworkMode::Offline
+    }
+}
+
+impl NetworkMode {
+    /// Mode of the game: offline or `--connect`ed to a server.
+    pub fn from_args() -> Self {
+        if let Some(address) = arg_value("--connect") {
+            match resolve(&address) {
+                Ok(server) => {
+                    return NetworkMode::Client {
+                        server,
+                        name: arg_value("--name").unwrap_or_else(|| "Player".to_string()),
+                    };
+                }
+                Err(e) => println!("Invalid server address {}: {:?}", address, e),
+            }
+        }
+        NetworkMode::Offline
+    }
+
+    /// Mode of the dedicated server, listening on every interface unless given `--address`.
+    pub fn server_from_args() -> io::Result<Self> {
+        let address = arg_value("--address").unwrap_or_else(|| "0.0.0.0".to_string());
+        resolve(&address).map(NetworkMode::Server)
+    }
+
+    pub fn is_server(&self) -> bool {
+        matches!(self, NetworkMode::Server(_))
+    }
+
+    /// Clients leave combat, monsters and spells to the server and only mirror its world.
+    pub fn is_authoritative(&self) -> bool {
+        !matches!(self, NetworkMode::Client { .. })
+    }
+}
+
+/// Value following `flag` on the command line.
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != flag);
+    args.next();
+    args.next().filter(|value| !value.starts_with("--"))
+}
+
+/// Parses `host:port` or just `host`, then using `DEFAULT_PORT`.
+fn resolve(address: &str) -> io::Result<SocketAddr> {
+    let with_port = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, DEFAULT_PORT)
+    };
+    with_port
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address"))
+}
+
+/// Whether the plugin being built should simulate the world, see `NetworkMode::is_authoritative`.
+pub fn is_authoritative(app: &mut AppBuilder) -> bool {
+    app.world()
+        .get_resource::<NetworkMode>()
+        .map_or(true, NetworkMode::is_authoritative)
+}
+
+/// `DefaultPlugins` without window, renderer and audio, ticking at 60 frames per second.
+pub fn add_headless_plugins(app: &mut AppBuilder) {
+    app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
+        1. / 60.,
+    )))
+    .insert_resource::<Box<dyn RenderResourceContext>>(Box::new(
+        HeadlessRenderResourceContext::default(),
+    ))
+    .add_plugins_with(DefaultPlugins, |group| {
+        group
+            .disable::<WinitPlugin>()
+            .disable::<WgpuPlugin>()
+            .disable::<AudioPlugin>()
+    })
+    .add_plugin(ScheduleRunnerPlugin::default());
+}
+
+/// `add_headless_plugins` for tests: no renderer nor log subscriber, so several apps fit in one
+/// process, and no runner, they are stepped with `App::update`.
+#[cfg(test)]
+pub fn add_test_plugins(app: &mut AppBuilder) {
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(bevy::asset::AssetPlugin)
+        .add_asset::<ColorMaterial>()
+        .init_resource::<Actions>();
+}
+
+/// Identifies an entity across server and clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NetworkId(pub u32);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ClientMessage {
+    Join {
+        name: String,
+        #[serde(default)]
+        vocation: Vocation,
+    },
+    Move(i32, i32),
+    Target(Option<u32>),
+    Cast {
+        entry: HotbarEntry,
+        target: Option<u32>,
+    },
+    Party(PartyCommand),
+    Ping,
+    Leave,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EntityKind {
+    Player,
+    Monster,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityState {
+    pub id: u32,
+    pub kind: EntityKind,
+    pub name: String,
+    pub tile: TilePos,
+    pub health: (f32, f32),
+    pub mana: (f32, f32),
+    pub level: u32,
+    /// `None` for monsters.
+    pub vocation: Option<Vocation>,
+    /// Id of the party leader.
+    pub party: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome {
+        id: u32,
+    },
+    /// One of the `parts` datagrams the world of server tick `tick` is split into.
+    State {
+        tick: u32,
+        parts: u32,
+        entities: Vec<EntityState>,
+    },
+    Damage {
+        id: u32,
+        value: f32,
+    },
+    Message(GameMessage),
+}
+
+fn send<T: Serialize>(socket: &UdpSocket, address: SocketAddr, message: &T) {
+    let result = ron::ser::to_string(message)
+        .map_err(|e| format!("{:?}", e))
+        .and_then(|data| {
+            socket
+                .send_to(data.as_bytes(), address)
+                .map_err(|e| format!("{:?}", e))
+        });
+    if let Err(e) = result {
+        println!("Failed to send to {}: {}", address, e);
+    }
+}
+
+/// Reads every datagram waiting on the non-blocking `socket`.
+fn receive<T: DeserializeOwned>(socket: &UdpSocket) -> Vec<(SocketAddr, T)> {
+    let mut buffer = vec![0; MAX_DATAGRAM];
+    let mut messages = Vec::new();
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((len, address)) => match ron::de::from_bytes(&buffer[..len]) {
+                Ok(message) => messages.push((address, message)),
+                Err(e) => println!("Invalid message from {}: {:?}", address, e),
+            },
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            // Reported on Windows when a previous datagram could not be delivered.
+            Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+            Err(e) => {
+                println!("Failed to receive: {:?}", e);
+                break;
+            }
+        }
+    }
+    messages
+}
+
+/// Authoritative server and mirroring client over UDP, messages are ron encoded.
+/// On one machine: `server` then `gamedev --connect 127.0.0.1 --name Bob`.
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let mode = app
+            .world()
+            .get_resource::<NetworkMode>()
+            .cloned()
+            .unwrap_or_default();
+        match mode {
+            NetworkMode::Offline => {}
+            NetworkMode::Server(address) => match Server::bind(address) {
+                Ok(server) => {
+                    println!("Listening on {}", address);
+                    app.insert_resource(server)
+                        .add_system(server_receive.system().label("network"))
+                        // Deaths are despawned in `Update`, removals only show up in a later stage.
+                        .add_system_to_stage(CoreStage::PostUpdate, server_clients.system())
+                        .add_system(assign_network_ids.system().after("network"))
+                        .add_system(server_player_messages.system())
+                        .add_system(server_broadcast.system());
+                }
+                Err(e) => println!("Failed to listen on {}: {:?}", address, e),
+            },
+            NetworkMode::Client { server, name } => match Client::connect(server, name) {
+                Ok(client) => {
+                    app.insert_resource(client)
+                        .add_system_set(
+                            SystemSet::on_enter(AppState::Playing).with_system(rejoin.system()),
+                        )
+                        .add_system_set(
+                            SystemSet::on_update(AppState::Playing)
+                                .with_system(client_receive.system())
+                                .with_system(client_send.system().after("actions")),
+                        );
+                }
+                Err(e) => println!("Failed to connect to {}: {:?}", server, e),
+            },
+        }
+    }
+}
+
+/// A player connected to this server.
+pub struct RemoteClient {
+    pub entity: Entity,
+    last_seen: f64,
+}
+
+pub struct Server {
+    socket: UdpSocket,
+    pub clients: HashMap<SocketAddr, RemoteClient>,
+    next_id: u32,
+    tick: Timer,
+    /// Number of the last state sent.
+    state_tick: u32,
+}
+
+impl Server {
+    fn bind(address: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Server {
+            socket,
+            clients: HashMap::new(),
+            next_id: 0,
+            tick: Timer::from_seconds(NETWORK_TICK, true),
+            state_tick: 0,
+        })
+    }
+
+    fn new_id(&mut self) -> NetworkId {
+        self.next_id += 1;
+        NetworkId(self.next_id)
+    }
+}
+
+fn entity_of(ids: &Query<(Entity, &NetworkId)>, id: u32) -> Option<Entity> {
+    ids.iter()
+        .find(|(_, network_id)| network_id.0 == id)
+        .map(|(entity, _)| entity)
+}
+
+fn server_receive(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
+    mut server: ResMut<Server>,
+    ids: Query<(Entity, &NetworkId)>,
+    mut targets: Query<&mut LockedTarget>,
+    mut casters: Query<(&Mana, &Vocation, &mut Vec<Item>)>,
+    mut cooldowns: Query<&mut Cooldowns>,
+    tiles: Query<&TilePos>,
+    bodies: Query<&TilePos, With<Body>>,
+    tile_map: Res<TileMap>,
+    mut move_events: EventWriter<MoveEvent>,
+    mut cast_events: EventWriter<CastEvent>,
+    mut party_events: EventWriter<PartyEvent>,
+) {
+    let server = &mut *server;
+    // Bodies spawned for joins only show up in `bodies` next frame.
+    let mut blocked: HashSet<TilePos> = bodies.iter().copied().collect();
+    blocked.extend(tile_map.walls.iter().copied());
+    for (address, message) in receive::<ClientMessage>(&server.socket) {
+        if let ClientMessage::Join { name, vocation } = &message {
+            let id = match server.clients.get(&address) {
+                Some(client) => ids.get(client.entity).map(|(_, id)| *id).ok(),
+                None if server.clients.len() >= MAX_CLIENTS => {
+                    let full = GameMessage::error("The server is full.");
+                    send(&server.socket, address, &ServerMessage::Message(full));
+                    None
+                }
+                None => {
+                    let tile = match nearest_free_tile(TilePos::new(0, 0), &blocked, SPAWN_RADIUS) {
+                        Some(tile) => tile,
+                        None => continue,
+                    };
+                    blocked.insert(tile);
+                    let name: String = name.chars().take(MAX_NAME_LENGTH).collect();
+                    let id = server.new_id();
+                    let entity = spawn_player_body(
+                        &mut commands,
+                        &mut materials,
+                        &name,
+                        *vocation,
+                        tile.to_world(tile_map.tile_size, 0.),
+                        tile_map.tile_size,
+                    );
+                    commands.entity(entity).insert(id);
+                    server.clients.insert(
+                        address,
+                        RemoteClient {
+                            entity,
+                            last_seen: 0.,
+                        },
+                    );
+                    println!("{} joined from {}", name, address);
+                    Some(id)
+                }
+            };
+            if let Some(id) = id {
+                send(
+                    &server.socket,
+                    address,
+                    &ServerMessage::Welcome { id: id.0 },
+                );
+            }
+        }
+        let client = match server.clients.get_mut(&address) {
+            Some(client) => client,
+            None => continue,
+        };
+        client.last_seen = time.seconds_since_startup();
+        match message {
+            // Validated against the speed timer and collisions by `movement_system`.
+            ClientMessage::Move(x, y) => move_events.send(MoveEvent(
+                client.entity,
+                IVec2::new(x.clamp(-1, 1), y.clamp(-1, 1)),
+            )),
+            // Attacked by `attack_system` like any other player's target.
+            ClientMessage::Target(id) => {
+                if let Ok(mut target) = targets.get_mut(client.entity) {
+                    target.0 = id
+                        .and_then(|id| entity_of(&ids, id))
+                        .filter(|target| *target != client.entity);
+                }
+            }
+            ClientMessage::Cast { entry, target } => {
+                let target = target.and_then(|id| entity_of(&ids, id));
+                let mut cooldowns = match cooldowns.get_mut(client.entity) {
+                    Ok(cooldowns) => cooldowns,
+                    Err(_) => continue,
+                };
+                match prepare_cast(
+                    &entry,
+                    client.entity,
+                    target,
+                    &mut cooldowns,
+                    &mut casters,
+                    &tiles,
+                    &tile_map,
+                ) {
+                    Ok(cast) => cast_events.send(cast),
+                    Err(e) => send(
+                        &server.socket,
+                        address,
+                        &ServerMessage::Message(GameMessage::error(e)),
+                    ),
+                }
+            }
+            ClientMessage::Party(command) => party_events.send(PartyEvent {
+                player: client.entity,
+                command,
+            }),
+            ClientMessage::Leave => {
+                commands.entity(client.entity).despawn_recursive();
+                server.clients.remove(&address);
+                println!("{} left", address);
+            }
+            ClientMessage::Join { .. } | ClientMessage::Ping => {}
+        }
+    }
+}
+
+/// Ticks client cooldowns and drops clients that went silent or whose player died.
+fn server_clients(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut server: ResMut<Server>,
+    mut cooldowns: Query<&mut Cooldowns>,
+    removed_players: RemovedComponents<Player>,
+) {
+    let now = time.seconds_since_startup();
+    let dead: Vec<Entity> = removed_players.iter().collect();
+    server.clients.retain(|address, client| {
+        if let Ok(mut cooldowns) = cooldowns.get_mut(client.entity) {
+            cooldowns.tick(time.delta());
+        }
+        if now - client.last_seen > CLIENT_TIMEOUT {
+            println!("{} timed out", address);
+            commands.entity(client.entity).despawn_recursive();
+            return false;
+        }
+        !dead.contains(&client.entity)
+    });
+}
+
+/// Ids the bodies spawned by the simulation, such as respawned monsters. Runs after
+/// `server_receive`, whose joined bodies come with the id sent in their `Welcome`.
+fn assign_network_ids(
+    mut commands: Commands,
+    mut server: ResMut<Server>,
+    bodies: Query<Entity, (With<Body>, Without<NetworkId>)>,
+) {
+    for entity in bodies.iter() {
+        let id = server.new_id();
+        commands.entity(entity).insert(id);
+    }
+}
+
+/// Sends messages meant for a remote player to its client.
+fn server_player_messages(server: Res<Server>, mut events: EventReader<PlayerMessage>) {
+    for PlayerMessage(entity, message) in events.iter() {
+        for (address, client) in server.clients.iter() {
+            if client.entity == *entity {
+                send(
+                    &server.socket,
+                    *address,
+                    &ServerMessage::Message(message.clone()),
+                );
+            }
+        }
+    }
+}
+
+fn server_broadcast(
+    time: Res<Time>,
+    mut server: ResMut<Server>,
+    bodies: Query<(
+        &NetworkId,
+        Option<&Player>,
+        &Name,
+        &TilePos,
+        &Health,
+        &Mana,
+        Option<&Level>,
+        Option<&Vocation>,
+        Option<&PartyMember>,
+    )>,
+    ids: Query<&NetworkId>,
+    mut damage_events: EventReader<DamageEvent>,
+) {
+    let mut messages: Vec<ServerMessage> = damage_events
+        .iter()
+        .filter_map(|damage| {
+            let id = ids.get(damage.defender).ok()?;
+            Some(ServerMessage::Damage {
+                id: id.0,
+                value: damage.damage.0.iter().map(|d| d.value).sum(),
+            })
+        })
+        .collect();
+    if server.tick.tick(time.delta()).just_finished() {
+        let states = bodies
+            .iter()
+            .map(
+                |(id, player, name, tile, health, mana, level, vocation, member)| EntityState {
+                    id: id.0,
+                    kind: if player.is_some() {
+                        EntityKind::Player
+                    } else {
+                        EntityKind::Monster
+                    },
+                    name: name.value.clone(),
+                    tile: *tile,
+                    health: (health.value, health.max_value),
+                    mana: (mana.value, mana.max_value),
+                    level: level.map_or(0, |level| level.0),
+                    vocation: vocation.copied(),
+                    party: member
+                        .and_then(|member| ids.get(member.leader).ok())
+                        .map(|id| id.0),
+                },
+            )
+            .collect();
+        server.state_tick += 1;
+        messages.extend(state_messages(server.state_tick, states));
+    }
+    for message in messages.iter() {
+        for address in server.clients.keys() {
+            send(&server.socket, *address, message);
+        }
+    }
+}
+
+/// Splits the world of `tick` into `State` messages that each fit in a datagram.
+fn state_messages(tick: u32, states: Vec<EntityState>) -> Vec<ServerMessage> {
+    let mut chunks: Vec<Vec<EntityState>> = vec![Vec::new()];
+    let mut size = 0;
+    for state in states {
+        // The separating comma included.
+        let len = ron::ser::to_string(&state).map_or(0, |data| data.len()) + 1;
+        if size + len > MAX_DATAGRAM - STATE_OVERHEAD && size > 0 {
+            chunks.push(Vec::new());
+            size = 0;
+        }
+        size += len;
+        chunks.last_mut().unwrap().push(state);
+    }
+    let parts = chunks.len() as u32;
+    chunks
+        .into_iter()
+        .map(|entities| ServerMessage::State {
+            tick,
+            parts,
+            entities,
+        })
+        .collect()
+}
+
+/// Parts of the latest `State` received so far.
+#[derive(Default)]
+struct PartialState {
+    tick: u32,
+    received: u32,
+    entities: Vec<EntityState>,
+    /// Whether `tick` was already handed out, late duplicates of it are ignored.
+    complete: bool,
+}
+
+impl PartialState {
+    /// Adds a part, returning the whole world once every part of its tick arrived. Parts of an
+    /// older tick still missing then are dropped, the next tick replaces them.
+    fn add(
+        &mut self,
+        tick: u32,
+        parts: u32,
+        entities: Vec<EntityState>,
+    ) -> Option<Vec<EntityState>> {
+        if tick != self.tick {
+            *self = PartialState {
+                tick,
+                ..Default::default()
+            };
+        }
+        if self.complete {
+            return None;
+        }
+        self.received += 1;
+        self.entities.extend(entities);
+        if self.received < parts {
+            return None;
+        }
+        self.complete = true;
+        Some(std::mem::take(&mut self.entities))
+    }
+}
+
+pub struct Client {
+    socket: UdpSocket,
+    server: SocketAddr,
+    name: String,
+    /// Id of our player, once the server accepted us.
+    id: Option<u32>,
+    entities: HashMap<u32, Entity>,
+    state: PartialState,
+    ping: Timer,
+}
+
+impl Client {
+    fn connect(server: SocketAddr, name: String) -> io::Result<Self> {
+        let local = if server.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        Ok(Client {
+            socket,
+            server,
+            name,
+            id: None,
+            entities: HashMap::new(),
+            state: PartialState::default(),
+            ping: Timer::from_seconds(PING_INTERVAL, true),
+        })
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        send(&self.socket, self.server, &ClientMessage::Leave);
+    }
+}
+
+/// Joins again after going through the menus, the world was cleared in between.
+fn rejoin(mut client: ResMut<Client>) {
+    client.id = None;
+    client.entities.clear();
+    client.state = PartialState::default();
+}
+
+fn client_send(
+    time: Res<Time>,
+    mut client: ResMut<Client>,
+    player: Res<LocalPlayer>,
+    character: Res<NewCharacter>,
+    targets: Query<(&LockedTarget, ChangeTrackers<LockedTarget>)>,
+    actions: Res<Actions>,
+    hotbars: Query<(&PlayerInput, &Hotbar)>,
+    ids: Query<&NetworkId>,
+    mut move_events: EventReader<MoveEvent>,
+    mut party_events: EventReader<PartyEvent>,
+) {
+    let mut messages = Vec::new();
+    if client.ping.tick(time.delta()).just_finished() {
+        messages.push(match client.id {
+            Some(_) => ClientMessage::Ping,
+            None => ClientMessage::Join {
+                name: client.name.clone(),
+                vocation: character.vocation,
+            },
+        });
+    }
+    if client.id.is_some() {
+        // Moves were already predicted locally, the server state corrects them.
+        for event in move_events.iter().filter(|event| player.0 == Some(event.0)) {
+            messages.push(ClientMessage::Move(event.1.x, event.1.y));
+        }
+        for event in party_events
+            .iter()
+            .filter(|event| player.0 == Some(event.player))
+        {
+            messages.push(ClientMessage::Party(event.command.clone()));
+        }
+        let (target, changed) = match player.0.map(|player| targets.get(player)) {
+            Some(Ok((target, tracker))) => (target.0, tracker.is_changed()),
+            _ => (None, false),
+        };
+        let target_id = target.and_then(|t| ids.get(t).ok()).map(|id| id.0);
+        if changed {
+            messages.push(ClientMessage::Target(target_id));
+        }
+        if let Some(Ok((input, hotbar))) = player.0.map(|player| hotbars.get(player)) {
+            for (slot, entry) in hotbar.slots.iter().enumerate() {
+                if let Some(entry) = entry {
+                    if actions.just_pressed_by(input.0, Action::Hotbar(slot as u8)) {
+                        messages.push(ClientMessage::Cast {
+                            entry: entry.clone(),
+                            target: target_id,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    for message in messages.iter() {
+        send(&client.socket, client.server, message);
+    }
+}
+
+fn client_receive(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    mut client: ResMut<Client>,
+    mut player: ResMut<LocalPlayer>,
+    mut state: ResMut<State<AppState>>,
+    tile_map: Res<TileMap>,
+    mut bodies: Query<(&mut TilePos, &Transform, &mut Health, &mut Mana, &mut Level)>,
+    members: Query<&PartyMember>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    let client = &mut *client;
+    for (address, message) in receive::<ServerMessage>(&client.socket) {
+        if address != client.server {
+            continue;
+        }
+        match message {
+            ServerMessage::Welcome { id } => {
+                if client.id.is_none() {
+                    messages.send(GameMessage::system(format!(
+                        "Connected to {}.",
+                        client.server
+                    )));
+                }
+                client.id = Some(id);
+            }
+            ServerMessage::State {
+                tick,
+                parts,
+                entities,
+            } => {
+                let states = match client.state.add(tick, parts, entities) {
+                    Some(states) => states,
+                    None => continue,
+                };
+                let mut seen = HashSet::new();
+                for entity_state in states.iter() {
+                    seen.insert(entity_state.id);
+                    match client.entities.get(&entity_state.id) {
+                        Some(entity) => update_body(
+                            &mut commands,
+                            *entity,
+                            entity_state,
+                            &tile_map,
+                            &mut bodies,
+                        ),
+                        None => {
+                            let size = tile_map.tile_size;
+                            let translation = entity_state.tile.to_world(size, 0.);
+                            let name = &entity_state.name;
+                            let vocation = entity_state.vocation.unwrap_or_default();
+                            let entity = if client.id == Some(entity_state.id) {
+                                let entity = spawn_player(
+                                    &mut commands,
+                                    &mut materials,
+                                    name,
+                                    vocation,
+                                    translation,
+                                    size,
+                                    KEYBOARD_SLOT,
+                                );
+                                player.0 = Some(entity);
+                                entity
+                            } else if entity_state.kind == EntityKind::Player {
+                                spawn_player_body(
+                                    &mut commands,
+                                    &mut materials,
+                                    name,
+                                    vocation,
+                                    translation,
+                                    size,
+                                )
+                            } else {
+                                spawn_monster(
+                                    &mut commands,
+                                    &mut materials,
+                                    name,
+                                    translation,
+                                    size,
+                                )
+                            };
+                            commands
+                                .entity(entity)
+                                .insert(NetworkId(entity_state.id))
+                                .insert(Health {
+                                    value: entity_state.health.0,
+                                    max_value: entity_state.health.1,
+                                })
+                                .insert(Mana {
+                                    value: entity_state.mana.0,
+                                    max_value: entity_state.mana.1,
+                                })
+                                .insert(Level(entity_state.level));
+                            client.entities.insert(entity_state.id, entity);
+                        }
+                    }
+                }
+                // Leaders can come after their members, so parties are linked once all exist.
+                for entity_state in states.iter() {
+                    if let Some(entity) = client.entities.get(&entity_state.id) {
+                        let leader = entity_state
+                            .party
+                            .and_then(|id| client.entities.get(&id))
+                            .copied();
+                        if members.get(*entity).ok().map(|member| member.leader) != leader {
+                            match leader {
+                                Some(leader) => {
+                                    commands.entity(*entity).insert(PartyMember { leader })
+                                }
+                                None => commands.entity(*entity).remove::<PartyMember>(),
+                            };
+                        }
+                    }
+                }
+                let gone: Vec<u32> = client
+                    .entities
+                    .keys()
+                    .filter(|id| !seen.contains(id))
+                    .copied()
+                    .collect();
+                for id in gone {
+                    if let Some(entity) = client.entities.remove(&id) {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                    if client.id == Some(id) {
+                        if let Err(e) = state.set(AppState::GameOver) {
+                            println!("Failed to change screen: {:?}", e);
+                        }
+                    }
+                }
+            }
+            ServerMessage::Damage { id, value } => {
+                if let Some(entity) = client.entities.get(&id) {
+                    create_combat_text(
+                        *entity,
+                        format!("{:.0}", value),
+                        &mut commands,
+                        &asset_server,
+                        None,
+                        None,
+                        None,
+                        None,
+                    );
+                }
+            }
+            ServerMessage::Message(message) => messages.send(message),
+        }
+    }
+}
+
+/// Moves a mirrored entity to the tile the server has it on and copies its resources.
+fn update_body(
+    commands: &mut Commands,
+    entity: Entity,
+    entity_state: &EntityState,
+    tile_map: &TileMap,
+    bodies: &mut Query<(&mut TilePos, &Transform, &mut Health, &mut Mana, &mut Level)>,
+) {
+    if let Ok((mut tile, transform, mut health, mut mana, mut level)) = bodies.get_mut(entity) {
+        if *tile != entity_state.tile {
+            *tile = entity_state.tile;
+            commands.entity(entity).insert(MoveAnimation {
+                from: transform.translation,
+                to: entity_state
+                    .tile
+                    .to_world(tile_map.tile_size, transform.translation.z),
+                timer: Timer::from_seconds(NETWORK_TICK, false),
+            });
+        }
+        if (health.value, health.max_value) != entity_state.health {
+            health.value = entity_state.health.0;
+            health.max_value = entity_state.health.1;
+        }
+        if (mana.value, mana.max_value) != entity_state.mana {
+            mana.value = entity_state.mana.0;
+            mana.max_value = entity_state.mana.1;
+        }
+        if level.0 != entity_state.level {
+            level.0 = entity_state.level;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Polls the non-blocking `socket` until something arrives.
+    fn receive_one<T: DeserializeOwned>(socket: &UdpSocket) -> (SocketAddr, T) {
+        for _ in 0..200 {
+            if let Some(message) = receive(socket).into_iter().next() {
+                return message;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("nothing received");
+    }
+
+    fn connected() -> (Server, Client) {
+        let server = Server::bind(resolve("127.0.0.1:0").unwrap()).unwrap();
+        let address = server.socket.local_addr().unwrap();
+        let client = Client::connect(address, "Bob".to_string()).unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn join_is_welcomed() {
+        let (server, client) = connected();
+        let join = ClientMessage::Join {
+            name: client.name.clone(),
+            vocation: Vocation::Druid,
+        };
+        send(&client.socket, client.server, &join);
+
+        let (address, message) = receive_one::<ClientMessage>(&server.socket);
+        match message {
+            ClientMessage::Join { name, vocation } => {
+                assert_eq!(name, "Bob");
+                assert_eq!(vocation, Vocation::Druid);
+            }
+            other => panic!("expected a join, got {:?}", other),
+        }
+
+        send(&server.socket, address, &ServerMessage::Welcome { id: 7 });
+        let (from, message) = receive_one::<ServerMessage>(&client.socket);
+        assert_eq!(from, client.server);
+        assert!(matches!(message, ServerMessage::Welcome { id: 7 }));
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let (server, client) = connected();
+        send(&client.socket, client.server, &ClientMessage::Ping);
+        let (address, _) = receive_one::<ClientMessage>(&server.socket);
+
+        let state = EntityState {
+            id: 3,
+            kind: EntityKind::Player,
+            name: "Bob".to_string(),
+            tile: TilePos::new(-2, 5),
+            health: (80., 100.),
+            mana: (10., 50.),
+            level: 4,
+            vocation: Some(Vocation::Knight),
+            party: Some(1),
+        };
+        let message = ServerMessage::State {
+            tick: 1,
+            parts: 1,
+            entities: vec![state],
+        };
+        send(&server.socket, address, &message);
+        let states = match receive_one::<ServerMessage>(&client.socket).1 {
+            ServerMessage::State { entities, .. } => entities,
+            other => panic!("expected a state, got {:?}", other),
+        };
+        assert_eq!(states.len(), 1);
+        let state = &states[0];
+        assert_eq!(state.id, 3);
+        assert_eq!(state.kind, EntityKind::Player);
+        assert_eq!(state.name, "Bob");
+        assert_eq!(state.tile, TilePos::new(-2, 5));
+        assert_eq!(state.health, (80., 100.));
+        assert_eq!(state.mana, (10., 50.));
+        assert_eq!(state.level, 4);
+        assert_eq!(state.vocation, Some(Vocation::Knight));
+        assert_eq!(state.party, Some(1));
+    }
+
+    #[test]
+    fn large_state_is_split() {
+        let states: Vec<EntityState> = (0..2000)
+            .map(|id| EntityState {
+                id,
+                kind: EntityKind::Monster,
+                name: format!("Monster number {}", id),
+                tile: TilePos::new(id as i32, -(id as i32)),
+                health: (100., 100.),
+                mana: (0., 0.),
+                level: 1,
+                vocation: None,
+                party: None,
+            })
+            .collect();
+        let messages = state_messages(9, states);
+        assert!(messages.len() > 1);
+
+        let mut partial = PartialState::default();
+        let mut world = None;
+        for message in messages {
+            let data = ron::ser::to_string(&message).unwrap();
+            assert!(data.len() <= MAX_DATAGRAM);
+            if let ServerMessage::State {
+                tick,
+                parts,
+                entities,
+            } = message
+            {
+                assert_eq!(tick, 9);
+                assert!(world.is_none());
+                world = partial.add(tick, parts, entities);
+            }
+        }
+        let world = world.expect("every part arrived");
+        assert_eq!(world.len(), 2000);
+        assert!(partial.add(9, 1, vec![]).is_none());
+    }
+
+    /// A server and a client app on 127.0.0.1, stepped until the client mirrors its player.
+    fn joined() -> (App, App, Entity) {
+        let address = UdpSocket::bind("127.0.0.1:0")
+            .and_then(|socket| socket.local_addr())
+            .unwrap();
+        let mut server = App::build();
+        add_test_plugins(&mut server);
+        server
+            .add_state(AppState::Playing)
+            .insert_resource(NetworkMode::Server(address))
+            .add_plugins(crate::SimulationPlugins);
+        let mut client = App::build();
+        add_test_plugins(&mut client);
+        client
+            .add_state(AppState::Playing)
+            .insert_resource(NetworkMode::Client {
+                server: address,
+                name: "Bob".to_string(),
+            })
+            .add_plugins(crate::SimulationPlugins);
+        let (mut server, mut client) = (server.app, client.app);
+
+        for _ in 0..200 {
+            client.update();
+            server.update();
+            let body = server
+                .world
+                .get_resource::<Server>()
+                .and_then(|server| server.clients.values().next().map(|c| c.entity));
+            let mirrored = client.world.get_resource::<LocalPlayer>().unwrap().0;
+            if let (Some(body), Some(mirrored)) = (body, mirrored) {
+                let server_tile = server.world.get::<TilePos>(body).copied();
+                if server_tile.is_some() && server_tile == client.world.get(mirrored).copied() {
+                    return (server, client, body);
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("the client never mirrored its player");
+    }
+
+    /// Sends `moves` from the client's player in one frame, returns its tile on the server
+    /// before and once they were handled.
+    fn server_moves(
+        server: &mut App,
+        client: &mut App,
+        body: Entity,
+        moves: &[IVec2],
+    ) -> (TilePos, TilePos) {
+        // Past the speed interval of the previous move.
+        std::thread::sleep(Duration::from_millis(600));
+        server.update();
+        let before = *server.world.get::<TilePos>(body).unwrap();
+        let player = client
+            .world
+            .get_resource::<LocalPlayer>()
+            .unwrap()
+            .0
+            .unwrap();
+        let mut events = client
+            .world
+            .get_resource_mut::<bevy::app::Events<MoveEvent>>()
+            .unwrap();
+        for delta in moves {
+            events.send(MoveEvent(player, *delta));
+        }
+        client.update();
+        for _ in 0..20 {
+            std::thread::sleep(Duration::from_millis(5));
+            server.update();
+        }
+        (before, *server.world.get::<TilePos>(body).unwrap())
+    }
+
+    #[test]
+    fn server_validates_client_moves() {
+        let (mut server, mut client, body) = joined();
+        // The monsters of `setup_world` could stand in the way.
+        let monsters: Vec<Entity> = server
+            .world
+            .query_filtered::<Entity, With<crate::ai::Monster>>()
+            .iter(&server.world)
+            .collect();
+        for monster in monsters {
+            server.world.despawn(monster);
+        }
+
+        let up = IVec2::new(0, 1);
+        let (before, after) = server_moves(&mut server, &mut client, body, &[up, up]);
+        assert_eq!(after, before.offset(up), "the second move came too early");
+
+        // The walls of `setup_world` stand at x = 3.
+        *server.world.get_mut::<TilePos>(body).unwrap() = TilePos::new(2, 0);
+        let right = IVec2::new(1, 0);
+        let (before, after) = server_moves(&mut server, &mut client, body, &[right]);
+        assert_eq!(before, TilePos::new(2, 0));
+        assert_eq!(after, before, "moved into a wall");
+    }
+}
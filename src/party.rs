@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{DamageTaken, Health},
+    console::{ConsoleAppExt, ConsoleCommand, ConsoleOutput},
+    entities::{Name, Player},
+    input::{slot_player, PlayerInput, KEYBOARD_SLOT},
+    map::TilePos,
+    messages::{GameMessage, PlayerMessage},
+    network::is_authoritative,
+    states::AppState,
+    LocalPlayer,
+};
+
+pub const MAX_PARTY_SIZE: usize = 5;
+/// Tiles from a dying creature within which members share its experience without having
+/// damaged it.
+pub const PARTY_SHARE_DISTANCE: f32 = 8.;
+/// Extra experience per member beyond the first, split with the rest.
+pub const PARTY_BONUS: f32 = 0.2;
+
+const PARTY_FRAME_WIDTH: f32 = 120.;
+
+pub struct PartyPlugin;
+
+impl Plugin for PartyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<PartyEvent>();
+        if is_authoritative(app) {
+            app.add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(party_events.system())
+                    .with_system(fix_parties.system()),
+            );
+        }
+    }
+}
+
+/// The `/party` command and the party frame.
+pub struct PartyUiPlugin;
+
+impl Plugin for PartyUiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_console_command(
+            "party",
+            "/party <invite|accept|leave|leader> [name]",
+            "Invites to, joins, leaves or hands over your party.",
+        )
+        .add_startup_system(setup_party_frame.system())
+        .add_system(party_command.system().after("console"))
+        .add_system(party_frame.system());
+    }
+}
+
+/// Every member points at the leader of its party, the leader included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PartyMember {
+    pub leader: Entity,
+}
+
+/// Pending invitation to the party of `leader`.
+#[derive(Debug)]
+pub struct PartyInvite {
+    pub leader: Entity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PartyCommand {
+    Invite(String),
+    Accept,
+    Leave,
+    Leader(String),
+}
+
+/// `command` issued by `player`, applied by the server or the offline game.
+#[derive(Debug)]
+pub struct PartyEvent {
+    pub player: Entity,
+    pub command: PartyCommand,
+}
+
+/// Members of the party led by `leader`, the leader first.
+pub fn party_members(leader: Entity, members: &Query<(Entity, &PartyMember)>) -> Vec<Entity> {
+    let mut party: Vec<Entity> = members
+        .iter()
+        .filter(|(_, member)| member.leader == leader)
+        .map(|(entity, _)| entity)
+        .collect();
+    party.sort_by_key(|entity| (*entity != leader, entity.id()));
+    party
+}
+
+/// Who gets the experience `attacker` earned on a creature: the members of its party that
+/// damaged the creature or stand near it, or only the attacker outside of a party.
+pub fn experience_receivers(
+    attacker: Entity,
+    tile: TilePos,
+    damage_taken: Option<&DamageTaken>,
+    members: &Query<(Entity, &PartyMember)>,
+    tiles: &Query<&TilePos>,
+    tile_size: f32,
+) -> Vec<Entity> {
+    let leader = match members.get(attacker) {
+        Ok((_, member)) => member.leader,
+        Err(_) => return vec![attacker],
+    };
+    party_members(leader, members)
+        .into_iter()
+        .filter(|member| {
+            *member == attacker
+                || damage_taken.map_or(false, |damage_taken| damage_taken.0.contains_key(member))
+                || tiles.get(*member).map_or(false, |t| {
+                    t.world_distance(tile, tile_size) <= PARTY_SHARE_DISTANCE * tile_size
+                })
+        })
+        .collect()
+}
+
+/// Experience each of `receivers` gets, including the party bonus.
+pub fn shared_experience(experience: u32, receivers: usize) -> u32 {
+    if receivers <= 1 {
+        return experience;
+    }
+    let total = experience as f32 * (1. + PARTY_BONUS * (receivers - 1) as f32);
+    (total / receivers as f32).round() as u32
+}
+
+fn party_command(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    players: Query<(Entity, &PlayerInput)>,
+    mut party_events: EventWriter<PartyEvent>,
+) {
+    for command in console_commands.iter().filter(|c| c.name == "party") {
+        let name = command.args[1.min(command.args.len())..].join(" ");
+        let party_command = match command.args.first().map(|arg| arg.as_str()) {
+            Some("invite") if !name.is_empty() => PartyCommand::Invite(name),
+            Some("accept") => PartyCommand::Accept,
+            Some("leave") => PartyCommand::Leave,
+            Some("leader") if !name.is_empty() => PartyCommand::Leader(name),
+            _ => {
+                output.send(ConsoleOutput(
+                    "Usage: /party <invite|accept|leave|leader> [name]".to_string(),
+                ));
+                continue;
+            }
+        };
+        if let Some(player) = slot_player(&players, KEYBOARD_SLOT) {
+            party_events.send(PartyEvent {
+                player,
+                command: party_command,
+            });
+        }
+    }
+}
+
+fn party_events(
+    mut commands: Commands,
+    mut events: EventReader<PartyEvent>,
+    names: Query<(Entity, &Name), With<Player>>,
+    members: Query<(Entity, &PartyMember)>,
+    invites: Query<&PartyInvite>,
+    mut messages: EventWriter<PlayerMessage>,
+) {
+    let name_of = |entity| {
+        names
+            .get(entity)
+            .map_or("Someone".to_string(), |(_, name)| name.value.clone())
+    };
+    let find_player = |name: &str| {
+        names
+            .iter()
+            .find(|(_, n)| n.value.eq_ignore_ascii_case(name))
+            .map(|(entity, _)| entity)
+            .ok_or_else(|| format!("There is no player named {}.", name))
+    };
+    for event in events.iter() {
+        let player = event.player;
+        let party = members.get(player).ok().map(|(_, member)| member.leader);
+        let mut notices: Vec<(Entity, String)> = Vec::new();
+        let result = match &event.command {
+            PartyCommand::Invite(name) => find_player(name).and_then(|target| {
+                if target == player {
+                    return Err("You can't invite yourself.".to_string());
+                }
+                if let Some(leader) = party {
+                    if leader != player {
+                        return Err("Only the party leader can invite.".to_string());
+                    }
+                    if party_members(leader, &members).len() >= MAX_PARTY_SIZE {
+                        return Err("Your party is full.".to_string());
+                    }
+                }
+                if members.get(target).is_ok() {
+                    return Err(format!("{} is already in a party.", name_of(target)));
+                }
+                commands
+                    .entity(target)
+                    .insert(PartyInvite { leader: player });
+                notices.push((
+                    player,
+                    format!("You invited {} to your party.", name_of(target)),
+                ));
+                notices.push((
+                    target,
+                    format!(
+                        "{} invited you to a party, type /party accept to join.",
+                        name_of(player)
+                    ),
+                ));
+                Ok(())
+            }),
+            PartyCommand::Accept => match invites.get(player) {
+                Ok(invite) => {
+                    let leader = invite.leader;
+                    commands.entity(player).remove::<PartyInvite>();
+                    // The inviter may have quit or joined another party since.
+                    let expired = names.get(leader).is_err()
+                        || members
+                            .get(leader)
+                            .map_or(false, |(_, member)| member.leader != leader);
+                    let mut joined = party_members(leader, &members);
+                    if joined.is_empty() {
+                        joined.push(leader);
+                    }
+                    if party.is_some() {
+                        Err("You are already in a party.".to_string())
+                    } else if expired {
+                        Err("The invitation expired.".to_string())
+                    } else if joined.len() >= MAX_PARTY_SIZE {
+                        Err("The party is full.".to_string())
+                    } else {
+                        for member in joined {
+                            notices
+                                .push((member, format!("{} joined the party.", name_of(player))));
+                        }
+                        commands.entity(leader).insert(PartyMember { leader });
+                        commands.entity(player).insert(PartyMember { leader });
+                        notices.push((
+                            player,
+                            format!("You joined the party of {}.", name_of(leader)),
+                        ));
+                        Ok(())
+                    }
+                }
+                Err(_) => Err("You have no party invitation.".to_string()),
+            },
+            PartyCommand::Leave => match party {
+                Some(leader) => {
+                    commands.entity(player).remove::<PartyMember>();
+                    for member in party_members(leader, &members) {
+                        if member != player {
+                            notices.push((member, format!("{} left the party.", name_of(player))));
+                        }
+                    }
+                    notices.push((player, "You left the party.".to_string()));
+                    Ok(())
+                }
+                None => Err("You are not in a party.".to_string()),
+            },
+            PartyCommand::Leader(name) => match party {
+                Some(leader) if leader == player => find_player(name).and_then(|target| {
+                    let party = party_members(leader, &members);
+                    if !party.contains(&target) {
+                        return Err(format!("{} is not in your party.", name_of(target)));
+                    }
+                    for member in party {
+                        commands
+                            .entity(member)
+                            .insert(PartyMember { leader: target });
+                        notices.push((
+                            member,
+                            format!("{} is now the party leader.", name_of(target)),
+                        ));
+                    }
+                    Ok(())
+                }),
+                Some(_) => Err("Only the party leader can hand it over.".to_string()),
+                None => Err("You are not in a party.".to_string()),
+            },
+        };
+        if let Err(e) = result {
+            messages.send(PlayerMessage(player, GameMessage::error(e)));
+        }
+        for (entity, text) in notices {
+            messages.send(PlayerMessage(entity, GameMessage::party(text)));
+        }
+    }
+}
+
+/// Promotes a new leader when the old one left or died, and disbands parties of one.
+fn fix_parties(
+    mut commands: Commands,
+    names: Query<&Name>,
+    members: Query<(Entity, &PartyMember)>,
+    mut messages: EventWriter<PlayerMessage>,
+) {
+    let mut parties: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    for (entity, member) in members.iter() {
+        parties.entry(member.leader).or_default().push(entity);
+    }
+    for (leader, mut party) in parties {
+        if party.len() == 1 {
+            commands.entity(party[0]).remove::<PartyMember>();
+            messages.send(PlayerMessage(
+                party[0],
+                GameMessage::party("Your party was disbanded."),
+            ));
+            continue;
+        }
+        if party.contains(&leader) {
+            continue;
+        }
+        party.sort_by_key(|entity| entity.id());
+        let new_leader = party[0];
+        let name = names
+            .get(new_leader)
+            .map_or("Someone".to_string(), |name| name.value.clone());
+        for member in party {
+            commands
+                .entity(member)
+                .insert(PartyMember { leader: new_leader });
+            messages.send(PlayerMessage(
+                member,
+                GameMessage::party(format!("{} is now the party leader.", name)),
+            ));
+        }
+    }
+}
+
+pub struct PartyFrameSlot(usize);
+
+pub struct PartyFrameName(usize);
+
+pub struct PartyFrameHealth(usize);
+
+fn setup_party_frame(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/font.ttf"),
+        font_size: 11.0,
+        color: Color::WHITE,
+    };
+    let slot_material = materials.add(Color::rgba(0.1, 0.1, 0.1, 0.8).into());
+    let bar_material = materials.add(Color::rgba(0., 0., 0., 0.8).into());
+    let fill_material = materials.add(Color::GREEN.into());
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(25.),
+                    left: Val::Px(5.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for slot in 0..MAX_PARTY_SIZE - 1 {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            display: Display::None,
+                            flex_direction: FlexDirection::ColumnReverse,
+                            padding: Rect::all(Val::Px(3.)),
+                            margin: Rect::all(Val::Px(2.)),
+                            ..Default::default()
+                        },
+                        material: slot_material.clone(),
+                        ..Default::default()
+                    })
+                    .insert(PartyFrameSlot(slot))
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    "",
+                                    text_style.clone(),
+                                    Default::default(),
+                                ),
+                                ..Default::default()
+                            })
+                            .insert(PartyFrameName(slot));
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(PARTY_FRAME_WIDTH), Val::Px(6.)),
+                                    ..Default::default()
+                                },
+                                material: bar_material.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                parent
+                                    .spawn_bundle(NodeBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                            ..Default::default()
+                                        },
+                                        material: fill_material.clone(),
+                                        ..Default::default()
+                                    })
+                                    .insert(PartyFrameHealth(slot));
+                            });
+                    });
+            }
+        });
+}
+
+/// Shows the other members of the `LocalPlayer`'s party with their health.
+fn party_frame(
+    player: Res<LocalPlayer>,
+    members: Query<(Entity, &PartyMember)>,
+    bodies: Query<(&Name, &Health)>,
+    mut slots: Query<(&mut Style, &PartyFrameSlot)>,
+    mut names: Query<(&mut Text, &PartyFrameName)>,
+    mut fills: Query<(&mut Style, &PartyFrameHealth), Without<PartyFrameSlot>>,
+) {
+    let shown: Vec<(String, f32)> = player
+        .0
+        .and_then(|player| {
+            let (_, member) = members.get(player).ok()?;
+            Some((player, member.leader))
+        })
+        .map_or(Vec::new(), |(player, leader)| {
+            party_members(leader, &members)
+                .into_iter()
+                .filter(|member| *member != player)
+                .filter_map(|member| {
+                    let (name, health) = bodies.get(member).ok()?;
+                    let label = if member == leader {
+                        format!("{} (leader)", name.value)
+                    } else {
+                        name.value.clone()
+                    };
+                    let percent = (health.value / health.max_value).max(0.).min(1.) * 100.;
+                    Some((label, percent))
+                })
+                .collect()
+        });
+
+    for (mut style, slot) in slots.iter_mut() {
+        let display = if slot.0 < shown.len() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    for (mut text, label) in names.iter_mut() {
+        if let Some((name, _)) = shown.get(label.0) {
+            if text.sections[0].value != *name {
+                text.sections[0].value = name.clone();
+            }
+        }
+    }
+    for (mut style, fill) in fills.iter_mut() {
+        if let Some((_, percent)) = shown.get(fill.0) {
+            if style.size.width != Val::Percent(*percent) {
+                style.size.width = Val::Percent(*percent);
+            }
+        }
+    }
+}
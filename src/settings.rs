@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use bevy::{prelude::*, window::WindowMode};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{read_ron, write_ron};
+
+pub const SETTINGS_PATH: &str = "config/settings.ron";
+
+/// Resolutions offered by the settings screen.
+pub const RESOLUTIONS: [(f32, f32); 4] =
+    [(800., 600.), (1024., 768.), (1280., 720.), (1920., 1080.)];
+pub const UI_SCALES: [f64; 4] = [0.75, 1., 1.25, 1.5];
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(apply_settings.system())
+            .add_system(save_settings.system());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScreenMode {
+    Windowed,
+    Fullscreen,
+    Borderless,
+}
+
+impl ScreenMode {
+    fn window_mode(self) -> WindowMode {
+        match self {
+            ScreenMode::Windowed => WindowMode::Windowed,
+            ScreenMode::Fullscreen => WindowMode::Fullscreen { use_size: true },
+            ScreenMode::Borderless => WindowMode::BorderlessFullscreen,
+        }
+    }
+}
+
+/// User settings, loaded at startup and written back whenever they change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub resolution: (f32, f32),
+    pub screen_mode: ScreenMode,
+    pub vsync: bool,
+    /// Multiplies the monitor scale factor, which scales the world along with the UI.
+    pub ui_scale: f64,
+    pub show_health_bars: bool,
+    pub combat_text: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            resolution: RESOLUTIONS[0],
+            screen_mode: ScreenMode::Windowed,
+            vsync: false,
+            ui_scale: 1.,
+            show_health_bars: true,
+            combat_text: true,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Self {
+        match read_ron(path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                println!("Using default settings ({:?}): {:?}", path, e);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: "GameDev".to_string(),
+            width: self.resolution.0,
+            height: self.resolution.1,
+            vsync: self.vsync,
+            resizable: true,
+            mode: self.screen_mode.window_mode(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Pushes changed settings to the primary window.
+fn apply_settings(settings: Res<Settings>, mut windows: ResMut<Windows>) {
+    if !settings.is_changed() {
+        return;
+    }
+    let window = match windows.get_primary_mut() {
+        Some(window) => window,
+        None => return,
+    };
+    let (width, height) = settings.resolution;
+    if window.requested_width() != width || window.requested_height() != height {
+        window.set_resolution(width, height);
+    }
+    let mode = settings.screen_mode.window_mode();
+    if window.mode() != mode {
+        window.set_mode(mode);
+    }
+    if window.vsync() != settings.vsync {
+        window.set_vsync(settings.vsync);
+    }
+    let scale_factor = Some(window.backend_scale_factor() * settings.ui_scale);
+    if window.scale_factor_override() != scale_factor {
+        window.set_scale_factor_override(scale_factor);
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    if settings.is_changed() {
+        if let Err(e) = write_ron(Path::new(SETTINGS_PATH), &*settings) {
+            println!("Failed to save settings: {:?}", e);
+        }
+    }
+}
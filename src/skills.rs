@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    item::Equipments,
+    messages::{GameMessage, PlayerMessage},
+    network::is_authoritative,
+    states::AppState,
+};
+
+/// Level fighting skills start at, where they neither help nor hinder.
+pub const FIGHTING_START: u32 = 10;
+/// Tries from the starting level of a fighting skill to the next one, then 10% more per level.
+const FIGHTING_TRIES: f32 = 50.;
+/// Mana to spend from magic level 0 to 1, then 10% more per level.
+const MAGIC_TRIES: f32 = 400.;
+const TRIES_GROWTH: f32 = 1.1;
+
+/// Hit chance in percent gained per weapon skill level.
+const HIT_CHANCE_PER_LEVEL: f32 = 1.;
+const DAMAGE_PER_LEVEL: f32 = 0.02;
+const BLOCK_PER_SHIELDING: f32 = 0.5;
+const SPELL_POWER_PER_MAGIC_LEVEL: f32 = 0.05;
+
+pub struct SkillsPlugin;
+
+impl Plugin for SkillsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<SkillTryEvent>();
+        if is_authoritative(app) {
+            app.add_system_set(
+                SystemSet::on_update(AppState::Playing).with_system(train_skills.system()),
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Skill {
+    Fist,
+    Sword,
+    Axe,
+    Club,
+    Distance,
+    Shielding,
+    MagicLevel,
+}
+
+pub const SKILLS: [Skill; 7] = [
+    Skill::Fist,
+    Skill::Sword,
+    Skill::Axe,
+    Skill::Club,
+    Skill::Distance,
+    Skill::Shielding,
+    Skill::MagicLevel,
+];
+
+impl Skill {
+    pub fn title(self) -> &'static str {
+        match self {
+            Skill::Fist => "Fist fighting",
+            Skill::Sword => "Sword fighting",
+            Skill::Axe => "Axe fighting",
+            Skill::Club => "Club fighting",
+            Skill::Distance => "Distance fighting",
+            Skill::Shielding => "Shielding",
+            Skill::MagicLevel => "Magic level",
+        }
+    }
+
+    fn starting_level(self) -> u32 {
+        match self {
+            Skill::MagicLevel => 0,
+            _ => FIGHTING_START,
+        }
+    }
+
+    /// Tries needed to advance from `level`, mana spent for the magic level.
+    pub fn tries_for(self, level: u32) -> u32 {
+        let (base, above_start) = match self {
+            Skill::MagicLevel => (MAGIC_TRIES, level),
+            _ => (FIGHTING_TRIES, level.saturating_sub(FIGHTING_START)),
+        };
+        (base * TRIES_GROWTH.powi(above_start as i32)).round() as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SkillProgress {
+    pub level: u32,
+    pub tries: u32,
+}
+
+/// Skills of a player, trained by using them. Missing skills are at their starting level.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Skills(pub HashMap<Skill, SkillProgress>);
+
+impl Skills {
+    pub fn level(&self, skill: Skill) -> u32 {
+        self.0
+            .get(&skill)
+            .map_or(skill.starting_level(), |progress| progress.level)
+    }
+
+    /// Progress towards the next level, in percent.
+    pub fn percent(&self, skill: Skill) -> f32 {
+        let tries = self.0.get(&skill).map_or(0, |progress| progress.tries);
+        tries as f32 / skill.tries_for(self.level(skill)) as f32 * 100.
+    }
+
+    /// Adds `tries` to `skill`, returning its new level when it advanced.
+    pub fn train(&mut self, skill: Skill, tries: u32) -> Option<u32> {
+        let progress = self.0.entry(skill).or_insert(SkillProgress {
+            level: skill.starting_level(),
+            tries: 0,
+        });
+        let start = progress.level;
+        progress.tries += tries;
+        while progress.tries >= skill.tries_for(progress.level) {
+            progress.tries -= skill.tries_for(progress.level);
+            progress.level += 1;
+        }
+        Some(progress.level).filter(|level| *level != start)
+    }
+}
+
+/// Level of `skill` for an entity that may have no skills, like monsters.
+pub fn skill_level(skills: Option<&Skills>, skill: Skill) -> u32 {
+    skills.map_or(skill.starting_level(), |skills| skills.level(skill))
+}
+
+/// Skill of the weapon in the main hand, fists when it has none.
+pub fn weapon_skill(equipments: &Equipments) -> Skill {
+    equipments
+        .mainhand
+        .as_ref()
+        .and_then(|item| item.skill)
+        .unwrap_or(Skill::Fist)
+}
+
+/// Level that counts for hitting and damage with a weapon of `skill`. Wands and rods take
+/// `MagicLevel`, which starts at 0, so they hit as if at the fighting start.
+pub fn weapon_level(skills: Option<&Skills>, skill: Skill) -> u32 {
+    match skill {
+        Skill::MagicLevel => FIGHTING_START,
+        _ => skill_level(skills, skill),
+    }
+}
+
+pub fn holds_shield(equipments: &Equipments) -> bool {
+    equipments
+        .offhand
+        .as_ref()
+        .map_or(false, |item| item.skill == Some(Skill::Shielding))
+}
+
+/// Chance in percent to hit with a base `rate` and a weapon skill at `level`.
+pub fn hit_chance(rate: f32, level: u32) -> f32 {
+    (rate + (level as f32 - FIGHTING_START as f32) * HIT_CHANCE_PER_LEVEL).min(100.)
+}
+
+pub fn weapon_damage_factor(level: u32) -> f32 {
+    1. + (level as f32 - FIGHTING_START as f32) * DAMAGE_PER_LEVEL
+}
+
+/// Physical damage a shield blocks on top of the equipment's block.
+pub fn shield_block(shielding: u32) -> f32 {
+    shielding as f32 * BLOCK_PER_SHIELDING
+}
+
+pub fn spell_power_factor(magic_level: u32) -> f32 {
+    1. + magic_level as f32 * SPELL_POWER_PER_MAGIC_LEVEL
+}
+
+/// A use of `skill`: a swing, a hit taken on a shield or mana spent.
+#[derive(Debug)]
+pub struct SkillTryEvent {
+    pub entity: Entity,
+    pub skill: Skill,
+    pub tries: u32,
+}
+
+fn train_skills(
+    mut events: EventReader<SkillTryEvent>,
+    mut query: Query<&mut Skills>,
+    mut messages: EventWriter<PlayerMessage>,
+) {
+    for event in events.iter() {
+        if let Ok(mut skills) = query.get_mut(event.entity) {
+            if let Some(level) = skills.train(event.skill, event.tries) {
+                let skill = event.skill.title().to_lowercase();
+                let text = match event.skill {
+                    Skill::MagicLevel => format!("You advanced to {} {}.", skill, level),
+                    _ => format!("You advanced to {} level {}.", skill, level),
+                };
+                messages.send(PlayerMessage(event.entity, GameMessage::system(text)));
+            }
+        }
+    }
+}
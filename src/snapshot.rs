@@ -0,0 +1,495 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{spawn_monster, ExperiencePoints, Monster, SpawnPoint},
+    combat::{Bestiary, Health, LockedTarget, Mana},
+    config::{read_ron, write_ron, ConfigError},
+    entities::{
+        spawn_player, spawn_player_body, Body, CurrentExperience, Level, Name, NextLevelExperience,
+        Player, Speed,
+    },
+    hotbar::Hotbar,
+    input::KEYBOARD_SLOT,
+    input_map::{Action, Actions},
+    item::{spawn_ground_item, Equipments, GroundItem, Item},
+    map::{ExploredTiles, TileMap, TilePos},
+    messages::GameMessage,
+    skills::Skills,
+    states::AppState,
+    vocation::Vocation,
+    LocalPlayer,
+};
+
+pub const QUICKSAVE_PATH: &str = "saves/quicksave.ron";
+
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<SaveSnapshotEvent>()
+            .add_event::<LoadSnapshotEvent>()
+            .insert_resource(StartupSnapshot(startup_snapshot_arg()))
+            .add_system(save_snapshot.system())
+            .add_system(load_snapshot.system());
+    }
+}
+
+/// Quick save and quick load keys.
+pub struct SnapshotHotkeysPlugin;
+
+impl Plugin for SnapshotHotkeysPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::Playing)
+                .with_system(snapshot_hotkeys.system().after("actions")),
+        );
+    }
+}
+
+/// Snapshot passed with `--snapshot <file>`, loaded instead of the default world.
+pub struct StartupSnapshot(pub Option<PathBuf>);
+
+pub fn startup_snapshot_arg() -> Option<PathBuf> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--snapshot");
+    args.next();
+    args.next().map(PathBuf::from)
+}
+
+pub struct SaveSnapshotEvent(pub PathBuf);
+pub struct LoadSnapshotEvent(pub PathBuf);
+
+/// Entities are stored by their `Entity::to_bits` id at save time and remapped on load.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub local_player: Option<u64>,
+    pub players: Vec<PlayerSnapshot>,
+    pub monsters: Vec<MonsterSnapshot>,
+    pub ground_items: Vec<GroundItemSnapshot>,
+    pub spawn_points: Vec<SpawnPointSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    pub id: u64,
+    pub name: String,
+    pub tile: TilePos,
+    pub health: Health,
+    pub mana: Mana,
+    pub speed: f32,
+    pub level: u32,
+    pub current_experience: u32,
+    pub next_level_experience: u32,
+    pub equipments: Equipments,
+    pub inventory: Vec<Item>,
+    #[serde(default)]
+    pub explored: HashSet<TilePos>,
+    #[serde(default)]
+    pub locked_target: Option<u64>,
+    #[serde(default)]
+    pub bestiary: HashMap<String, u32>,
+    #[serde(default)]
+    pub vocation: Vocation,
+    #[serde(default)]
+    pub skills: Skills,
+    #[serde(default)]
+    pub hotbar: Hotbar,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MonsterSnapshot {
+    pub id: u64,
+    pub name: String,
+    pub tile: TilePos,
+    pub health: Health,
+    pub mana: Mana,
+    pub speed: f32,
+    #[serde(default = "first_level")]
+    pub level: u32,
+    pub experience: u32,
+    pub enemy: Option<u64>,
+    pub vision_range: f32,
+}
+
+/// Level of monsters saved before they had one.
+fn first_level() -> u32 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroundItemSnapshot {
+    pub item: Item,
+    pub position: Vec3,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpawnPointSnapshot {
+    pub monster: String,
+    pub position: Vec3,
+    pub respawn: f32,
+    pub elapsed: f32,
+    pub spawned: Option<u64>,
+}
+
+pub fn read_snapshot(path: &Path) -> Result<WorldSnapshot, ConfigError> {
+    read_ron(path)
+}
+
+fn snapshot_hotkeys(
+    actions: Res<Actions>,
+    mut save_events: EventWriter<SaveSnapshotEvent>,
+    mut load_events: EventWriter<LoadSnapshotEvent>,
+) {
+    if actions.just_pressed(Action::QuickSave) {
+        save_events.send(SaveSnapshotEvent(PathBuf::from(QUICKSAVE_PATH)));
+    }
+    if actions.just_pressed(Action::QuickLoad) {
+        load_events.send(LoadSnapshotEvent(PathBuf::from(QUICKSAVE_PATH)));
+    }
+}
+
+fn save_snapshot(
+    mut events: EventReader<SaveSnapshotEvent>,
+    players: Query<
+        (
+            Entity,
+            &Name,
+            &TilePos,
+            &Health,
+            &Mana,
+            &Speed,
+            &Level,
+            &CurrentExperience,
+            &NextLevelExperience,
+            &Equipments,
+            &Vec<Item>,
+            &ExploredTiles,
+            &LockedTarget,
+            &Bestiary,
+            &Vocation,
+        ),
+        With<Player>,
+    >,
+    skills: Query<&Skills>,
+    hotbars: Query<&Hotbar>,
+    monsters: Query<(
+        Entity,
+        &Name,
+        &TilePos,
+        &Health,
+        &Mana,
+        &Speed,
+        &Level,
+        &ExperiencePoints,
+        &Monster,
+    )>,
+    ground_items: Query<(&GroundItem, &Transform)>,
+    spawn_points: Query<&SpawnPoint>,
+    localplayer: Res<LocalPlayer>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    for event in events.iter() {
+        let mut snapshot = WorldSnapshot {
+            local_player: localplayer.0.map(|p| p.to_bits()),
+            ..Default::default()
+        };
+        for (
+            entity,
+            name,
+            tile,
+            health,
+            mana,
+            speed,
+            lvl,
+            cur,
+            next,
+            equipments,
+            inventory,
+            explored,
+            target,
+            bestiary,
+            vocation,
+        ) in players.iter()
+        {
+            snapshot.players.push(PlayerSnapshot {
+                id: entity.to_bits(),
+                name: name.value.clone(),
+                tile: *tile,
+                health: health.clone(),
+                mana: mana.clone(),
+                speed: speed.value,
+                level: lvl.0,
+                current_experience: cur.0,
+                next_level_experience: next.0,
+                equipments: equipments.clone(),
+                inventory: inventory.clone(),
+                explored: explored.0.clone(),
+                locked_target: target.0.map(|t| t.to_bits()),
+                bestiary: bestiary.0.clone(),
+                vocation: *vocation,
+                skills: skills.get(entity).cloned().unwrap_or_default(),
+                hotbar: hotbars.get(entity).cloned().unwrap_or_default(),
+            });
+        }
+        for (entity, name, tile, health, mana, speed, level, experience, monster) in monsters.iter()
+        {
+            snapshot.monsters.push(MonsterSnapshot {
+                id: entity.to_bits(),
+                name: name.value.clone(),
+                tile: *tile,
+                health: health.clone(),
+                mana: mana.clone(),
+                speed: speed.value,
+                level: level.0,
+                experience: experience.0,
+                enemy: monster.enemy.map(|e| e.to_bits()),
+                vision_range: monster.vision_range,
+            });
+        }
+        for (ground_item, transform) in ground_items.iter() {
+            snapshot.ground_items.push(GroundItemSnapshot {
+                item: ground_item.0.clone(),
+                position: transform.translation,
+            });
+        }
+        for spawn_point in spawn_points.iter() {
+            snapshot.spawn_points.push(SpawnPointSnapshot {
+                monster: spawn_point.monster.clone(),
+                position: spawn_point.position,
+                respawn: spawn_point.timer.duration().as_secs_f32(),
+                elapsed: spawn_point.timer.elapsed_secs(),
+                spawned: spawn_point.spawned.map(|e| e.to_bits()),
+            });
+        }
+        match write_ron(&event.0, &snapshot) {
+            Ok(_) => messages.send(GameMessage::system(format!(
+                "Snapshot saved to {:?}.",
+                event.0
+            ))),
+            Err(e) => messages.send(GameMessage::error(format!(
+                "Failed to save snapshot {:?}: {:?}",
+                event.0, e
+            ))),
+        }
+    }
+}
+
+fn load_snapshot(
+    mut commands: Commands,
+    mut events: EventReader<LoadSnapshotEvent>,
+    existing: Query<Entity, Or<(With<Body>, With<GroundItem>, With<SpawnPoint>)>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    tile_map: Res<TileMap>,
+    mut localplayer: ResMut<LocalPlayer>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    for event in events.iter() {
+        let snapshot = match read_snapshot(&event.0) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                messages.send(GameMessage::error(format!(
+                    "Failed to load snapshot {:?}: {:?}",
+                    event.0, e
+                )));
+                continue;
+            }
+        };
+        for entity in existing.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        restore_snapshot(
+            &mut commands,
+            &mut materials,
+            &snapshot,
+            &mut localplayer,
+            tile_map.tile_size,
+        );
+    }
+}
+
+/// Spawns every entity in `snapshot` and returns the saved id -> new entity map.
+pub fn restore_snapshot(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    snapshot: &WorldSnapshot,
+    localplayer: &mut LocalPlayer,
+    tile_size: f32,
+) -> HashMap<u64, Entity> {
+    let mut entities = HashMap::new();
+
+    for player in snapshot.players.iter() {
+        let translation = player.tile.to_world(tile_size, 0.);
+        let entity = if snapshot.local_player == Some(player.id) {
+            spawn_player(
+                commands,
+                materials,
+                &player.name,
+                player.vocation,
+                translation,
+                tile_size,
+                KEYBOARD_SLOT,
+            )
+        } else {
+            spawn_player_body(
+                commands,
+                materials,
+                &player.name,
+                player.vocation,
+                translation,
+                tile_size,
+            )
+        };
+        commands
+            .entity(entity)
+            .insert(player.health.clone())
+            .insert(player.mana.clone())
+            .insert(Speed {
+                value: player.speed,
+                ..Default::default()
+            })
+            .insert(Level(player.level))
+            .insert(CurrentExperience(player.current_experience))
+            .insert(NextLevelExperience(player.next_level_experience))
+            .insert(player.equipments.clone())
+            .insert(player.inventory.clone())
+            .insert(ExploredTiles(player.explored.clone()))
+            .insert(Bestiary(player.bestiary.clone()))
+            .insert(player.skills.clone())
+            .insert(player.hotbar.clone().resized());
+        entities.insert(player.id, entity);
+    }
+
+    for monster in snapshot.monsters.iter() {
+        let entity = spawn_monster(
+            commands,
+            materials,
+            &monster.name,
+            monster.tile.to_world(tile_size, 0.),
+            tile_size,
+        );
+        commands
+            .entity(entity)
+            .insert(monster.health.clone())
+            .insert(monster.mana.clone())
+            .insert(Speed {
+                value: monster.speed,
+                ..Default::default()
+            })
+            .insert(Level(monster.level))
+            .insert(ExperiencePoints(monster.experience));
+        entities.insert(monster.id, entity);
+    }
+
+    // References can point at any restored entity, so they are resolved after everything exists.
+    for player in snapshot.players.iter() {
+        commands.entity(entities[&player.id]).insert(LockedTarget(
+            player.locked_target.and_then(|e| entities.get(&e).copied()),
+        ));
+    }
+    for monster in snapshot.monsters.iter() {
+        commands.entity(entities[&monster.id]).insert(Monster {
+            enemy: monster.enemy.and_then(|e| entities.get(&e).copied()),
+            vision_range: monster.vision_range,
+        });
+    }
+
+    for ground_item in snapshot.ground_items.iter() {
+        spawn_ground_item(
+            commands,
+            materials,
+            ground_item.item.clone(),
+            ground_item.position,
+            tile_size,
+        );
+    }
+
+    for spawn_point in snapshot.spawn_points.iter() {
+        let mut timer = Timer::new(Duration::from_secs_f32(spawn_point.respawn), false);
+        timer.set_elapsed(Duration::from_secs_f32(spawn_point.elapsed));
+        commands.spawn().insert(SpawnPoint {
+            monster: spawn_point.monster.clone(),
+            position: spawn_point.position,
+            timer,
+            spawned: spawn_point.spawned.and_then(|e| entities.get(&e).copied()),
+        });
+    }
+
+    localplayer.0 = snapshot
+        .local_player
+        .and_then(|e| entities.get(&e).copied());
+
+    entities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{add_test_plugins, NetworkMode};
+    use bevy::app::Events;
+
+    fn send<T: Send + Sync + 'static>(app: &mut App, event: T) {
+        app.world
+            .get_resource_mut::<Events<T>>()
+            .unwrap()
+            .send(event);
+        app.update();
+        app.update();
+    }
+
+    fn name(app: &App, entity: Entity) -> String {
+        app.world.get::<Name>(entity).unwrap().value.clone()
+    }
+
+    #[test]
+    fn restore_remaps_references() {
+        let mut builder = App::build();
+        add_test_plugins(&mut builder);
+        builder
+            .add_state(AppState::Playing)
+            .insert_resource(NetworkMode::Offline)
+            .add_plugins(crate::SimulationPlugins);
+        let mut app = builder.app;
+        app.update();
+
+        let player = app.world.get_resource::<LocalPlayer>().unwrap().0.unwrap();
+        let mut monsters = app
+            .world
+            .query_filtered::<Entity, With<Monster>>()
+            .iter(&app.world)
+            .collect::<Vec<_>>();
+        monsters.sort_by_key(|monster| monster.to_bits());
+        let target = monsters[1];
+        let target_name = name(&app, target);
+        app.world.get_mut::<LockedTarget>(player).unwrap().0 = Some(target);
+        for monster in monsters.iter() {
+            app.world.get_mut::<Monster>(*monster).unwrap().enemy = Some(player);
+        }
+
+        let path = std::env::temp_dir().join(format!("snapshot-{}.ron", std::process::id()));
+        send(&mut app, SaveSnapshotEvent(path.clone()));
+        send(&mut app, LoadSnapshotEvent(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+
+        let restored = app.world.get_resource::<LocalPlayer>().unwrap().0.unwrap();
+        assert!(app.world.get_entity(player).is_none());
+        let locked = app.world.get::<LockedTarget>(restored).unwrap().0.unwrap();
+        assert!(!monsters.contains(&locked));
+        assert!(app.world.get::<Monster>(locked).is_some());
+        assert_eq!(name(&app, locked), target_name);
+
+        let enemies = app
+            .world
+            .query::<&Monster>()
+            .iter(&app.world)
+            .map(|monster| monster.enemy)
+            .collect::<Vec<_>>();
+        assert_eq!(enemies.len(), monsters.len());
+        assert!(enemies.iter().all(|enemy| *enemy == Some(restored)));
+    }
+}
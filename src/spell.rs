@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::combat::{
+    create_combat_text, Damage, DamageSet, DamageType, Health, Mana, ResistanceEvent, StatusEffect,
+    StatusEffects,
+};
+use crate::network::is_authoritative;
+use crate::skills::{skill_level, spell_power_factor, Skill, SkillTryEvent, Skills};
+use crate::states::AppState;
+use crate::vocation::Vocation;
+
+pub struct SpellPlugin;
+
+impl Plugin for SpellPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<CastEvent>();
+        if is_authoritative(app) {
+            app.add_system_set(
+                SystemSet::on_update(AppState::Playing).with_system(cast_system.system()),
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SpellTarget {
+    Caster,
+    Target,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SpellEffect {
+    Heal(f32),
+    RestoreMana(f32),
+    Damage(Damage),
+    /// Damage every second for the given seconds, shown as a status effect on the target.
+    DamageOverTime(Damage, f32),
+}
+
+/// Status effect name of damage over time of `dtype`.
+fn condition_name(dtype: DamageType) -> &'static str {
+    match dtype {
+        DamageType::Fire => "Burning",
+        DamageType::Earth => "Poisoned",
+        DamageType::Air => "Electrified",
+        DamageType::Water => "Drowning",
+        DamageType::Death => "Cursed",
+        _ => "Bleeding",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Spell {
+    pub id: String,
+    pub title: String,
+    pub mana_cost: f32,
+    /// Seconds.
+    pub cooldown: f32,
+    pub range: f32,
+    pub target: SpellTarget,
+    pub effect: SpellEffect,
+    /// Vocations that can cast it, everyone when empty.
+    pub vocations: Vec<Vocation>,
+}
+
+impl Spell {
+    pub fn new(id: &str) -> Option<Spell> {
+        match id {
+            "light_healing" => Some(Spell {
+                id: id.to_string(),
+                title: "Light Healing".to_string(),
+                mana_cost: 20.,
+                cooldown: 1.,
+                range: 0.,
+                target: SpellTarget::Caster,
+                effect: SpellEffect::Heal(40.),
+                vocations: vec![],
+            }),
+            "fire_strike" => Some(Spell {
+                id: id.to_string(),
+                title: "Fire Strike".to_string(),
+                mana_cost: 20.,
+                cooldown: 2.,
+                range: 130.,
+                target: SpellTarget::Target,
+                effect: SpellEffect::Damage(Damage {
+                    value: 25.,
+                    dtype: DamageType::Fire,
+                }),
+                vocations: vec![Vocation::Sorcerer, Vocation::Druid],
+            }),
+            "envenom" => Some(Spell {
+                id: id.to_string(),
+                title: "Envenom".to_string(),
+                mana_cost: 30.,
+                cooldown: 4.,
+                range: 130.,
+                target: SpellTarget::Target,
+                effect: SpellEffect::DamageOverTime(
+                    Damage {
+                        value: 6.,
+                        dtype: DamageType::Earth,
+                    },
+                    6.,
+                ),
+                vocations: vec![Vocation::Druid],
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A spell cast or an item used, already validated against cooldown, range and mana.
+#[derive(Debug)]
+pub struct CastEvent {
+    pub caster: Entity,
+    pub target: Entity,
+    pub mana_cost: f32,
+    pub effect: SpellEffect,
+}
+
+/// Mana spent trains the magic level, which strengthens the heals and damage that mana paid for.
+fn cast_system(
+    mut commands: Commands,
+    mut cast_events: EventReader<CastEvent>,
+    mut resistance_events: EventWriter<ResistanceEvent>,
+    mut skill_tries: EventWriter<SkillTryEvent>,
+    mut query: Query<(&mut Health, &mut Mana)>,
+    mut status_effects: Query<&mut StatusEffects>,
+    skills: Query<&Skills>,
+    asset_server: Res<AssetServer>,
+) {
+    for cast in cast_events.iter() {
+        if let Ok((_, mut mana)) = query.get_mut(cast.caster) {
+            mana.value -= cast.mana_cost;
+        }
+        let power = if cast.mana_cost > 0. {
+            skill_tries.send(SkillTryEvent {
+                entity: cast.caster,
+                skill: Skill::MagicLevel,
+                tries: cast.mana_cost.round() as u32,
+            });
+            spell_power_factor(skill_level(skills.get(cast.caster).ok(), Skill::MagicLevel))
+        } else {
+            1.
+        };
+        match cast.effect {
+            SpellEffect::Heal(value) => {
+                let value = value * power;
+                if let Ok((mut health, _)) = query.get_mut(cast.target) {
+                    health.value = (health.value + value).min(health.max_value);
+                    create_combat_text(
+                        cast.target,
+                        format!("+{:.0}", value),
+                        &mut commands,
+                        &asset_server,
+                        Some(Color::GREEN),
+                        None,
+                        None,
+                        None,
+                    );
+                }
+            }
+            SpellEffect::RestoreMana(value) => {
+                if let Ok((_, mut mana)) = query.get_mut(cast.target) {
+                    mana.value = (mana.value + value).min(mana.max_value);
+                    create_combat_text(
+                        cast.target,
+                        format!("+{:.0}", value),
+                        &mut commands,
+                        &asset_server,
+                        Some(Color::BLUE),
+                        None,
+                        None,
+                        None,
+                    );
+                }
+            }
+            SpellEffect::Damage(damage) => resistance_events.send(ResistanceEvent {
+                attacker: cast.caster,
+                defender: cast.target,
+                damage: DamageSet(vec![Damage {
+                    value: damage.value * power,
+                    ..damage
+                }]),
+            }),
+            SpellEffect::DamageOverTime(damage, seconds) => {
+                if let Ok(mut effects) = status_effects.get_mut(cast.target) {
+                    effects.apply(StatusEffect {
+                        name: condition_name(damage.dtype).to_string(),
+                        timer: Timer::from_seconds(seconds, false),
+                        damage: Some((
+                            cast.caster,
+                            Damage {
+                                value: damage.value * power,
+                                ..damage
+                            },
+                        )),
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,469 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    ai::SpawnPoint,
+    combat::DeathEvent,
+    entities::{BarVisibility, Body},
+    input::DestinationMarker,
+    input_map::{Action, Actions},
+    item::GroundItem,
+    map::{TileMap, Wall},
+    network::NetworkMode,
+    settings::{ScreenMode, Settings, RESOLUTIONS, UI_SCALES},
+    snapshot::{StartupSnapshot, QUICKSAVE_PATH},
+    vocation::{NewCharacter, Vocation, VOCATIONS},
+    LocalPlayer,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppState {
+    MainMenu,
+    CharacterCreation,
+    Settings,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+/// Main menu, character creation, settings, pause and game over screens.
+pub struct StatesPlugin;
+
+impl Plugin for StatesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system(menu_buttons.system())
+            .add_system(button_colors.system())
+            .add_system(pause_game.system().after("actions"))
+            .add_system_set(
+                SystemSet::on_enter(AppState::MainMenu)
+                    .with_system(clear_world.system())
+                    .with_system(main_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(despawn_screen.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::CharacterCreation)
+                    .with_system(character_creation.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::CharacterCreation)
+                    .with_system(despawn_screen.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Settings).with_system(settings_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Settings).with_system(despawn_screen.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Settings).with_system(settings_labels.system()),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(pause_menu.system()))
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused).with_system(despawn_screen.system()),
+            )
+            .add_system_set(SystemSet::on_update(AppState::Playing).with_system(game_over.system()))
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver).with_system(game_over_screen.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::GameOver).with_system(despawn_screen.system()),
+            );
+    }
+}
+
+/// State to start in: straight into the game when `--snapshot` is given or when hosting or
+/// joining a server.
+pub fn initial_state(startup_snapshot: &Option<PathBuf>, network: &NetworkMode) -> AppState {
+    if startup_snapshot.is_some() || *network != NetworkMode::Offline {
+        AppState::Playing
+    } else {
+        AppState::MainMenu
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuButton {
+    NewGame,
+    Vocation(Vocation),
+    Continue,
+    Settings,
+    Quit,
+    Resume,
+    ExitToMenu,
+    Resolution,
+    ScreenMode,
+    Vsync,
+    UiScale,
+    HealthBars,
+    BarVisibility,
+    CombatText,
+    Back,
+}
+
+/// Root of the UI of the current screen, despawned when leaving its state.
+pub struct Screen;
+
+pub struct ButtonLabel(pub MenuButton);
+
+pub struct MenuMaterials {
+    background: Handle<ColorMaterial>,
+    button: Handle<ColorMaterial>,
+    hovered: Handle<ColorMaterial>,
+    font: Handle<Font>,
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(MenuMaterials {
+        background: materials.add(Color::rgba(0., 0., 0., 0.7).into()),
+        button: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
+        hovered: materials.add(Color::rgb(0.3, 0.3, 0.3).into()),
+        font: asset_server.load("fonts/font.ttf"),
+    });
+}
+
+fn button_title(button: MenuButton) -> &'static str {
+    match button {
+        MenuButton::NewGame => "New game",
+        MenuButton::Vocation(vocation) => vocation.title(),
+        MenuButton::Continue => "Continue",
+        MenuButton::Settings => "Settings",
+        MenuButton::Quit => "Quit",
+        MenuButton::Resume => "Resume",
+        MenuButton::ExitToMenu => "Main menu",
+        MenuButton::Resolution => "Resolution",
+        MenuButton::ScreenMode => "Window mode",
+        MenuButton::Vsync => "Vsync",
+        MenuButton::UiScale => "UI scale",
+        MenuButton::HealthBars => "Health bars",
+        MenuButton::BarVisibility => "Show bars",
+        MenuButton::CombatText => "Combat text",
+        MenuButton::Back => "Back",
+    }
+}
+
+/// Full screen column with a title and one button per entry.
+fn spawn_screen(
+    commands: &mut Commands,
+    materials: &MenuMaterials,
+    title: &str,
+    buttons: &[MenuButton],
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.background.clone(),
+            ..Default::default()
+        })
+        .insert(Interaction::default())
+        .insert(Screen)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(10.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    title,
+                    TextStyle {
+                        font: materials.font.clone(),
+                        font_size: 32.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            for button in buttons.iter() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(200.), Val::Px(32.)),
+                            margin: Rect::all(Val::Px(4.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: materials.button.clone(),
+                        ..Default::default()
+                    })
+                    .insert(*button)
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    button_title(*button),
+                                    TextStyle {
+                                        font: materials.font.clone(),
+                                        font_size: 16.0,
+                                        color: Color::WHITE,
+                                    },
+                                    Default::default(),
+                                ),
+                                ..Default::default()
+                            })
+                            .insert(ButtonLabel(*button));
+                    });
+            }
+        });
+}
+
+fn despawn_screen(mut commands: Commands, screens: Query<Entity, With<Screen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn main_menu(mut commands: Commands, materials: Res<MenuMaterials>) {
+    let mut buttons = vec![MenuButton::NewGame];
+    if Path::new(QUICKSAVE_PATH).exists() {
+        buttons.push(MenuButton::Continue);
+    }
+    buttons.extend(&[MenuButton::Settings, MenuButton::Quit]);
+    spawn_screen(&mut commands, &materials, "GameDev", &buttons);
+}
+
+fn character_creation(mut commands: Commands, materials: Res<MenuMaterials>) {
+    let mut buttons: Vec<MenuButton> = VOCATIONS.iter().map(|v| MenuButton::Vocation(*v)).collect();
+    buttons.push(MenuButton::Back);
+    spawn_screen(&mut commands, &materials, "Choose a vocation", &buttons);
+}
+
+fn settings_menu(mut commands: Commands, materials: Res<MenuMaterials>) {
+    spawn_screen(
+        &mut commands,
+        &materials,
+        "Settings",
+        &[
+            MenuButton::Resolution,
+            MenuButton::ScreenMode,
+            MenuButton::Vsync,
+            MenuButton::UiScale,
+            MenuButton::HealthBars,
+            MenuButton::BarVisibility,
+            MenuButton::CombatText,
+            MenuButton::Back,
+        ],
+    );
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+/// Current value shown next to the title of a settings button.
+fn setting_value(
+    button: MenuButton,
+    settings: &Settings,
+    visibility: BarVisibility,
+) -> Option<String> {
+    let value = match button {
+        MenuButton::Resolution => format!("{}x{}", settings.resolution.0, settings.resolution.1),
+        MenuButton::ScreenMode => format!("{:?}", settings.screen_mode),
+        MenuButton::Vsync => on_off(settings.vsync).to_string(),
+        MenuButton::UiScale => format!("{:.0}%", settings.ui_scale * 100.),
+        MenuButton::HealthBars => on_off(settings.show_health_bars).to_string(),
+        MenuButton::BarVisibility => format!("{:?}", visibility),
+        MenuButton::CombatText => on_off(settings.combat_text).to_string(),
+        _ => return None,
+    };
+    Some(format!("{}: {}", button_title(button), value))
+}
+
+fn settings_labels(
+    settings: Res<Settings>,
+    visibility: Res<BarVisibility>,
+    mut labels: Query<(&mut Text, &ButtonLabel)>,
+) {
+    for (mut text, label) in labels.iter_mut() {
+        if let Some(value) = setting_value(label.0, &settings, *visibility) {
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
+        }
+    }
+}
+
+/// Entry after `current` in `values`, the first one when `current` is not listed.
+fn next_value<T: Copy + PartialEq>(values: &[T], current: T) -> T {
+    let index = values.iter().position(|v| *v == current);
+    values[index.map_or(0, |i| (i + 1) % values.len())]
+}
+
+fn pause_menu(mut commands: Commands, materials: Res<MenuMaterials>) {
+    spawn_screen(
+        &mut commands,
+        &materials,
+        "Paused",
+        &[MenuButton::Resume, MenuButton::ExitToMenu],
+    );
+}
+
+fn game_over_screen(mut commands: Commands, materials: Res<MenuMaterials>) {
+    spawn_screen(
+        &mut commands,
+        &materials,
+        "You are dead",
+        &[MenuButton::ExitToMenu],
+    );
+}
+
+fn button_colors(
+    materials: Res<MenuMaterials>,
+    mut buttons: Query<
+        (&Interaction, &mut Handle<ColorMaterial>),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut material) in buttons.iter_mut() {
+        *material = match interaction {
+            Interaction::None => materials.button.clone(),
+            _ => materials.hovered.clone(),
+        };
+    }
+}
+
+fn menu_buttons(
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut state: ResMut<State<AppState>>,
+    mut startup_snapshot: ResMut<StartupSnapshot>,
+    mut character: ResMut<NewCharacter>,
+    mut settings: ResMut<Settings>,
+    mut visibility: ResMut<BarVisibility>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let result = match button {
+            MenuButton::NewGame => state.set(AppState::CharacterCreation),
+            MenuButton::Vocation(vocation) => {
+                character.vocation = *vocation;
+                startup_snapshot.0 = None;
+                state.set(AppState::Playing)
+            }
+            MenuButton::Continue => {
+                startup_snapshot.0 = Some(PathBuf::from(QUICKSAVE_PATH));
+                state.set(AppState::Playing)
+            }
+            MenuButton::Settings => state.set(AppState::Settings),
+            MenuButton::Quit => {
+                exit.send(AppExit);
+                Ok(())
+            }
+            MenuButton::Resume => state.pop(),
+            MenuButton::ExitToMenu => state.replace(AppState::MainMenu),
+            MenuButton::Resolution => {
+                settings.resolution = next_value(&RESOLUTIONS, settings.resolution);
+                Ok(())
+            }
+            MenuButton::ScreenMode => {
+                settings.screen_mode = next_value(
+                    &[
+                        ScreenMode::Windowed,
+                        ScreenMode::Fullscreen,
+                        ScreenMode::Borderless,
+                    ],
+                    settings.screen_mode,
+                );
+                Ok(())
+            }
+            MenuButton::Vsync => {
+                settings.vsync = !settings.vsync;
+                Ok(())
+            }
+            MenuButton::UiScale => {
+                settings.ui_scale = next_value(&UI_SCALES, settings.ui_scale);
+                Ok(())
+            }
+            MenuButton::HealthBars => {
+                settings.show_health_bars = !settings.show_health_bars;
+                Ok(())
+            }
+            MenuButton::CombatText => {
+                settings.combat_text = !settings.combat_text;
+                Ok(())
+            }
+            MenuButton::BarVisibility => {
+                *visibility = match *visibility {
+                    BarVisibility::Always => BarVisibility::Damaged,
+                    BarVisibility::Damaged => BarVisibility::PlayerAndTarget,
+                    BarVisibility::PlayerAndTarget => BarVisibility::Always,
+                };
+                Ok(())
+            }
+            MenuButton::Back => state.set(AppState::MainMenu),
+        };
+        if let Err(e) = result {
+            println!("Failed to change screen: {:?}", e);
+        }
+    }
+}
+
+fn pause_game(actions: Res<Actions>, mut state: ResMut<State<AppState>>) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+    let result = match *state.current() {
+        AppState::Playing => state.push(AppState::Paused),
+        AppState::Paused => state.pop(),
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        println!("Failed to change screen: {:?}", e);
+    }
+}
+
+fn game_over(
+    player: Res<LocalPlayer>,
+    mut deaths: EventReader<DeathEvent>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if deaths.iter().any(|death| player.0 == Some(death.defender)) {
+        if let Err(e) = state.set(AppState::GameOver) {
+            println!("Failed to change screen: {:?}", e);
+        }
+    }
+}
+
+/// Despawns what a game session spawned so the next one starts clean.
+fn clear_world(
+    mut commands: Commands,
+    entities: Query<
+        Entity,
+        Or<(
+            With<Body>,
+            With<GroundItem>,
+            With<SpawnPoint>,
+            With<Wall>,
+            With<DestinationMarker>,
+        )>,
+    >,
+    mut tile_map: ResMut<TileMap>,
+    mut player: ResMut<LocalPlayer>,
+) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    tile_map.walls.clear();
+    player.0 = None;
+}
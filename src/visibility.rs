@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    map::{TileMap, TilePos, EXPLORE_RADIUS},
+    states::AppState,
+    Bars, LocalPlayer,
+};
+
+pub struct VisibilityPlugin;
+
+impl Plugin for VisibilityPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<VisibleTiles>().add_system_set(
+            SystemSet::on_update(AppState::Playing)
+                .with_system(update_visible_tiles.system().label("visible_tiles"))
+                .with_system(hide_unseen.system().after("visible_tiles")),
+        );
+    }
+}
+
+/// Tiles of the Bresenham line from `a` to `b`, both ends included.
+pub fn line(a: TilePos, b: TilePos) -> Vec<TilePos> {
+    let dx = (b.x - a.x).abs();
+    let dy = -(b.y - a.y).abs();
+    let step = IVec2::new((b.x - a.x).signum(), (b.y - a.y).signum());
+    let mut error = dx + dy;
+    let mut tile = a;
+    let mut line = vec![tile];
+    while tile != b {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            tile.x += step.x;
+        }
+        if doubled <= dx {
+            error += dx;
+            tile.y += step.y;
+        }
+        line.push(tile);
+    }
+    line
+}
+
+/// Whether no wall stands on the line between `a` and `b`, the ends themselves excluded. The
+/// line is drawn both ways and either will do, so `b` sees `a` whenever `a` sees `b`.
+pub fn has_line_of_sight(tile_map: &TileMap, a: TilePos, b: TilePos) -> bool {
+    let clear = |from, to| {
+        line(from, to)
+            .iter()
+            .all(|tile| *tile == a || *tile == b || !tile_map.is_wall(*tile))
+    };
+    clear(a, b) || clear(b, a)
+}
+
+/// Tiles within `radius` tiles of `origin` with a line of sight to it.
+pub fn visible_tiles(tile_map: &TileMap, origin: TilePos, radius: i32) -> HashSet<TilePos> {
+    let mut visible = HashSet::new();
+    for dx in -radius..=radius {
+        for dy in -radius..=radius {
+            let tile = origin.offset(IVec2::new(dx, dy));
+            if dx * dx + dy * dy <= radius * radius && has_line_of_sight(tile_map, origin, tile) {
+                visible.insert(tile);
+            }
+        }
+    }
+    visible
+}
+
+/// What the `LocalPlayer` sees, creatures elsewhere are not drawn.
+#[derive(Debug, Default)]
+pub struct VisibleTiles(pub HashSet<TilePos>);
+
+fn update_visible_tiles(
+    player: Res<LocalPlayer>,
+    tile_map: Res<TileMap>,
+    mut visible: ResMut<VisibleTiles>,
+    players: Query<(&TilePos, ChangeTrackers<TilePos>)>,
+) {
+    let (tile, tracker) = match player.0.and_then(|player| players.get(player).ok()) {
+        Some(player) => player,
+        None => return,
+    };
+    if tracker.is_changed() || tile_map.is_changed() {
+        visible.0 = visible_tiles(&tile_map, *tile, EXPLORE_RADIUS);
+    }
+}
+
+/// Hides creatures out of the `LocalPlayer`'s sight along with their name and combat text, the
+/// bars follow in `bar_visibility`.
+fn hide_unseen(
+    player: Res<LocalPlayer>,
+    visible: Res<VisibleTiles>,
+    mut creatures: Query<(&TilePos, &mut Visible, Option<&Children>)>,
+    mut children: Query<&mut Visible, (Without<TilePos>, Without<Bars>)>,
+) {
+    if player.0.is_none() {
+        return;
+    }
+    for (tile, mut sprite, descendants) in creatures.iter_mut() {
+        let seen = visible.0.contains(tile);
+        if sprite.is_visible != seen {
+            sprite.is_visible = seen;
+        }
+        for child in descendants.iter().flat_map(|children| children.iter()) {
+            if let Ok(mut child) = children.get_mut(*child) {
+                if child.is_visible != seen {
+                    child.is_visible = seen;
+                }
+            }
+        }
+    }
+}
+
+/// Whether `tile` is seen by the `LocalPlayer`, always true without one or without a tile.
+pub fn is_seen(player: &LocalPlayer, visible: &VisibleTiles, tile: Option<&TilePos>) -> bool {
+    player.0.is_none() || tile.map_or(true, |tile| visible.0.contains(tile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walls(tiles: &[(i32, i32)]) -> TileMap {
+        TileMap {
+            walls: tiles.iter().map(|(x, y)| TilePos::new(*x, *y)).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn wall_blocks_the_line() {
+        let tile_map = walls(&[(2, 0)]);
+        assert!(!has_line_of_sight(
+            &tile_map,
+            TilePos::new(0, 0),
+            TilePos::new(4, 0)
+        ));
+        assert!(has_line_of_sight(
+            &tile_map,
+            TilePos::new(0, 1),
+            TilePos::new(4, 1)
+        ));
+    }
+
+    #[test]
+    fn line_of_sight_is_symmetric() {
+        // The line from (0, 0) to (2, 1) crosses (1, 1), the one back crosses (1, 0).
+        let (a, b) = (TilePos::new(0, 0), TilePos::new(2, 1));
+        assert!(line(a, b).contains(&TilePos::new(1, 1)));
+        assert!(!line(b, a).contains(&TilePos::new(1, 1)));
+        let tile_map = walls(&[(1, 1)]);
+        assert!(has_line_of_sight(&tile_map, a, b));
+        assert!(has_line_of_sight(&tile_map, b, a));
+
+        let tile_map = walls(&[(1, 1), (2, -1), (-1, 2), (0, -2)]);
+        for ax in -3..=3 {
+            for ay in -3..=3 {
+                for bx in -3..=3 {
+                    for by in -3..=3 {
+                        let (a, b) = (TilePos::new(ax, ay), TilePos::new(bx, by));
+                        assert_eq!(
+                            has_line_of_sight(&tile_map, a, b),
+                            has_line_of_sight(&tile_map, b, a),
+                            "{:?} and {:?}",
+                            a,
+                            b
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn visible_tiles_stay_within_the_radius() {
+        let origin = TilePos::new(5, -5);
+        let visible = visible_tiles(&walls(&[]), origin, 2);
+        assert_eq!(visible.len(), 13);
+        assert!(visible.contains(&origin));
+        assert!(visible.contains(&TilePos::new(5, -3)));
+        assert!(!visible.contains(&TilePos::new(7, -4)));
+    }
+
+    #[test]
+    fn walls_at_the_ends_are_seen() {
+        let tile_map = walls(&[(3, 0)]);
+        let (a, wall) = (TilePos::new(0, 0), TilePos::new(3, 0));
+        assert!(has_line_of_sight(&tile_map, a, wall));
+        assert!(has_line_of_sight(&tile_map, wall, a));
+        let visible = visible_tiles(&tile_map, a, 5);
+        assert!(visible.contains(&wall));
+        assert!(!visible.contains(&TilePos::new(4, 0)));
+    }
+}
@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{Attack, Defense, Health, Mana},
+    entities::Level,
+    item::Capacity,
+};
+
+pub struct VocationPlugin;
+
+impl Plugin for VocationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(NewCharacter::from_args())
+            .add_system(vocation_stats.system());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Vocation {
+    Knight,
+    Paladin,
+    Sorcerer,
+    Druid,
+}
+
+pub const VOCATIONS: [Vocation; 4] = [
+    Vocation::Knight,
+    Vocation::Paladin,
+    Vocation::Sorcerer,
+    Vocation::Druid,
+];
+
+impl Default for Vocation {
+    fn default() -> Self {
+        Vocation::Knight
+    }
+}
+
+/// Stats a vocation has at a level, the base ones being those of `Combat::default`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VocationStats {
+    pub health: f32,
+    pub mana: f32,
+    pub capacity: f32,
+    pub attack: f32,
+    pub defense: f32,
+}
+
+const BASE_STATS: VocationStats = VocationStats {
+    health: 100.,
+    mana: 50.,
+    capacity: 400.,
+    attack: 10.,
+    defense: 1.,
+};
+
+impl Vocation {
+    pub fn from_name(name: &str) -> Option<Vocation> {
+        VOCATIONS
+            .iter()
+            .find(|vocation| vocation.title().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Vocation::Knight => "Knight",
+            Vocation::Paladin => "Paladin",
+            Vocation::Sorcerer => "Sorcerer",
+            Vocation::Druid => "Druid",
+        }
+    }
+
+    /// Gained on every level up. Stats follow the level, so a level set lower with `/level`
+    /// loses them again; dying costs no levels.
+    pub fn gains(self) -> VocationStats {
+        match self {
+            Vocation::Knight => VocationStats {
+                health: 15.,
+                mana: 5.,
+                capacity: 25.,
+                attack: 1.,
+                defense: 1.,
+            },
+            Vocation::Paladin => VocationStats {
+                health: 10.,
+                mana: 15.,
+                capacity: 20.,
+                attack: 0.8,
+                defense: 0.5,
+            },
+            Vocation::Sorcerer => VocationStats {
+                health: 5.,
+                mana: 30.,
+                capacity: 10.,
+                attack: 0.5,
+                defense: 0.25,
+            },
+            Vocation::Druid => VocationStats {
+                health: 5.,
+                mana: 30.,
+                capacity: 10.,
+                attack: 0.4,
+                defense: 0.3,
+            },
+        }
+    }
+
+    pub fn stats(self, level: u32) -> VocationStats {
+        let gains = self.gains();
+        let level = level as f32;
+        VocationStats {
+            health: BASE_STATS.health + gains.health * level,
+            mana: BASE_STATS.mana + gains.mana * level,
+            capacity: BASE_STATS.capacity + gains.capacity * level,
+            attack: BASE_STATS.attack + gains.attack * level,
+            defense: BASE_STATS.defense + gains.defense * level,
+        }
+    }
+
+    /// Whether a spell or an item restricted to `allowed` can be used, an empty list allows all.
+    pub fn is_allowed(self, allowed: &[Vocation]) -> bool {
+        allowed.is_empty() || allowed.contains(&self)
+    }
+}
+
+/// Vocation of the next character created on this machine, `--vocation <name>` or picked on the
+/// character creation screen.
+#[derive(Debug)]
+pub struct NewCharacter {
+    pub vocation: Vocation,
+}
+
+impl NewCharacter {
+    pub fn from_args() -> Self {
+        let mut args = std::env::args().skip_while(|arg| arg != "--vocation");
+        args.next();
+        let vocation = match args.next() {
+            Some(name) => Vocation::from_name(&name).unwrap_or_else(|| {
+                println!("Unknown vocation {}, playing a knight", name);
+                Vocation::default()
+            }),
+            None => Vocation::default(),
+        };
+        NewCharacter { vocation }
+    }
+}
+
+/// Sets the stats of players to those of their vocation at their level, so a lost level also
+/// loses what it gave. Current health and mana follow a raised maximum.
+fn vocation_stats(
+    mut query: Query<
+        (
+            &Vocation,
+            &Level,
+            &mut Health,
+            &mut Mana,
+            &mut Capacity,
+            &mut Attack,
+            &mut Defense,
+        ),
+        Or<(Changed<Vocation>, Changed<Level>)>,
+    >,
+) {
+    for (vocation, level, mut health, mut mana, mut capacity, mut attack, mut defense) in
+        query.iter_mut()
+    {
+        let stats = vocation.stats(level.0);
+        if health.max_value != stats.health {
+            health.value =
+                (health.value + (stats.health - health.max_value).max(0.)).min(stats.health);
+            health.max_value = stats.health;
+        }
+        if mana.max_value != stats.mana {
+            mana.value = (mana.value + (stats.mana - mana.max_value).max(0.)).min(stats.mana);
+            mana.max_value = stats.mana;
+        }
+        capacity.0 = stats.capacity;
+        attack.damage.value = stats.attack;
+        defense.value = stats.defense;
+    }
+}